@@ -1,9 +1,12 @@
+use crate::auth::{self, Auth};
 use crate::config::{EnvironmentConfig, GlobalAgentConfig};
+use crate::prometheus;
 use crate::provider::Agent;
+use crate::rate_limit::{self, RateLimiter};
 use crate::routes;
 use axum::extract::DefaultBodyLimit;
 use axum::http::{HeaderValue, header};
-use axum::middleware::map_response;
+use axum::middleware::{from_fn, map_response};
 use axum::response::Response;
 use axum::{Extension, Router};
 use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
@@ -22,6 +25,8 @@ pub async fn create_agent(
     config: EnvironmentConfig,
     global_config: Arc<GlobalAgentConfig>,
 ) -> Agent {
+    prometheus::handle();
+
     match Agent::new(config, global_config).await {
         Ok(agent) => agent,
         Err(error) => {
@@ -32,20 +37,52 @@ pub async fn create_agent(
 }
 
 pub async fn create_app(agent: Agent, config: EnvironmentConfig) -> Router<()> {
+    let auth = match Auth::new(&config).await {
+        Ok(auth) => auth,
+        Err(error) => {
+            tracing::error!("Failed to configure authentication: {error:?}");
+            panic!("Failed to configure authentication");
+        }
+    };
+
     let local_pool = LocalPoolHandle::new(available_parallelism().map(Into::into).unwrap_or(1));
 
-    let (router, openapi) = OpenApiRouter::with_openapi(openapi())
+    let rate_limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+    let project_routes = OpenApiRouter::new()
         .routes(routes!(routes::engine::evaluate))
+        .routes(routes!(routes::engine::evaluate_batch))
+        .routes(routes!(routes::engine::evaluate_stream))
         .routes(routes!(routes::project_info::project_info))
         .routes(routes!(routes::decision_points::decision_points))
+        .route_layer(from_fn(rate_limit::limit));
+
+    let (router, openapi) = OpenApiRouter::with_openapi(openapi())
+        .merge(project_routes)
+        .routes(routes!(routes::project_info::projects))
+        .routes(routes!(routes::admin::reload))
+        .routes(routes!(routes::admin::reload_project))
+        .routes(routes!(routes::admin::releases))
+        .routes(routes!(
+            routes::admin::pin_release,
+            routes::admin::unpin_release
+        ))
         .routes(routes!(routes::infra::version))
         .routes(routes!(routes::infra::health))
+        .routes(routes!(routes::infra::ready))
+        .routes(routes!(routes::infra::metrics))
         .split_for_parts();
 
+    let mut docs = Router::from(SwaggerUi::new("/api/docs").url("/api.json", openapi));
+    if config.require_metadata_auth {
+        docs = docs.route_layer(from_fn(auth::require_metadata_auth));
+    }
+
     let mut app = router
-        .merge(SwaggerUi::new("/api/docs").url("/api.json", openapi))
+        .merge(docs)
         .layer(Extension(agent))
+        .layer(Extension(auth))
         .layer(Extension(local_pool))
+        .layer(Extension(rate_limiter))
         .layer(DefaultBodyLimit::max(16 * 1024 * 1024))
         .layer(map_response(map_json_charset));
     if config.otel_enabled {
@@ -0,0 +1,99 @@
+use crate::audit::AuditRecord;
+use crate::config::FileAuditSinkConfig;
+use anyhow::Context;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+#[derive(Debug)]
+pub struct FileSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl FileSink {
+    pub async fn open(config: &FileAuditSinkConfig) -> anyhow::Result<Self> {
+        if let Some(parent) = config.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let file = open_append(&config.path).await?;
+        let size = file.metadata().await?.len();
+
+        Ok(Self {
+            path: config.path.clone(),
+            max_bytes: config.max_bytes,
+            max_files: config.max_files,
+            file,
+            size,
+        })
+    }
+
+    /// Appends the records as JSON lines, rotating first when they would not fit the current file.
+    pub async fn write(&mut self, records: &[AuditRecord]) -> anyhow::Result<()> {
+        let mut buffer = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut buffer, record).context("Failed to serialize record")?;
+            buffer.push(b'\n');
+        }
+
+        if self.size > 0 && self.size + buffer.len() as u64 > self.max_bytes {
+            self.rotate().await?;
+        }
+
+        self.file.write_all(&buffer).await?;
+        self.file.flush().await?;
+        self.file.sync_data().await?;
+        self.size += buffer.len() as u64;
+
+        Ok(())
+    }
+
+    /// Shifts `<path>.N` to `<path>.N+1`, dropping the oldest, and starts a new file.
+    async fn rotate(&mut self) -> anyhow::Result<()> {
+        self.file.flush().await?;
+
+        if self.max_files == 0 {
+            fs::remove_file(&self.path).await?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, index);
+                match fs::rename(&from, rotated_path(&self.path, index + 1)).await {
+                    Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+                    _ => {}
+                }
+            }
+
+            fs::rename(&self.path, rotated_path(&self.path, 1)).await?;
+        }
+
+        self.file = open_append(&self.path).await?;
+        self.size = 0;
+        tracing::info!(path = %self.path.display(), "Rotated audit log");
+
+        Ok(())
+    }
+}
+
+async fn open_append(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    name.into()
+}
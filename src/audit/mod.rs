@@ -0,0 +1,170 @@
+use crate::audit::file::FileSink;
+use crate::audit::webhook::WebhookSink;
+use crate::config::{AuditConfig, AuditSinkConfig};
+use crate::prometheus;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use strum_macros::AsRefStr;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+mod file;
+mod webhook;
+
+/// Records handed to the sink in a single write.
+const MAX_BATCH: usize = 100;
+
+/// Minimum time between two warnings about dropped records.
+const DROP_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// An evaluation as retained in the audit log.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub project: String,
+    pub decision: Arc<str>,
+    pub release_id: Option<Arc<str>>,
+    pub version_id: Option<Arc<str>>,
+    /// Access token fingerprint, client certificate name or JWT subject, absent for public projects
+    pub caller: Option<Arc<str>>,
+    pub context: Value,
+    pub output: Option<Value>,
+    pub error: Option<Value>,
+    pub latency_ms: f64,
+}
+
+#[derive(Debug, AsRefStr)]
+enum AuditSink {
+    File(FileSink),
+    Webhook(WebhookSink),
+}
+
+impl AuditSink {
+    async fn write(&mut self, records: &[AuditRecord]) -> anyhow::Result<()> {
+        match self {
+            AuditSink::File(file) => file.write(records).await,
+            AuditSink::Webhook(webhook) => webhook.write(records).await,
+        }
+    }
+}
+
+/// Hands records to a background writer, so evaluations never wait on the sink. While the
+/// buffer is full records are dropped and counted in `agent_audit_dropped_total`.
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    sender: Option<mpsc::Sender<AuditRecord>>,
+    close: CancellationToken,
+    writer: Mutex<Option<JoinHandle<()>>>,
+    /// Records dropped since the last warning
+    dropped: AtomicU64,
+    last_warning: Mutex<Option<Instant>>,
+}
+
+impl AuditLog {
+    /// Opens the configured sink, or creates a disabled log when auditing is not configured.
+    pub async fn new(config: Option<&AuditConfig>) -> anyhow::Result<Self> {
+        let Some(config) = config else {
+            return Ok(Self::default());
+        };
+
+        let sink = match &config.sink {
+            AuditSinkConfig::File(config) => AuditSink::File(FileSink::open(config).await?),
+            AuditSinkConfig::Webhook(config) => AuditSink::Webhook(WebhookSink::new(config)?),
+        };
+
+        let (sender, receiver) = mpsc::channel(config.buffer.max(1));
+        let close = CancellationToken::new();
+        let writer = tokio::spawn(write_records(sink, receiver, close.clone()));
+
+        Ok(Self {
+            sender: Some(sender),
+            close,
+            writer: Mutex::new(Some(writer)),
+            ..Default::default()
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    pub fn record(&self, record: AuditRecord) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+
+        if let Err(error) = sender.try_send(record) {
+            prometheus::record_audit_dropped();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            self.warn_dropped(&error.to_string());
+        }
+    }
+
+    /// Logs the records dropped since the last warning, at most once per interval.
+    fn warn_dropped(&self, error: &str) {
+        let Ok(mut last_warning) = self.last_warning.try_lock() else {
+            return;
+        };
+        if last_warning.is_some_and(|at| at.elapsed() < DROP_WARNING_INTERVAL) {
+            return;
+        }
+
+        *last_warning = Some(Instant::now());
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        tracing::warn!(error, dropped, "Audit records dropped");
+    }
+
+    /// Stops accepting records and waits up to `timeout` for the buffered ones to be written.
+    pub async fn close(&self, timeout: Duration) {
+        self.close.cancel();
+
+        let writer = self.writer.lock().ok().and_then(|mut writer| writer.take());
+        if let Some(writer) = writer
+            && tokio::time::timeout(timeout, writer).await.is_err()
+        {
+            tracing::warn!("Audit records were not written before the shutdown timeout");
+        }
+    }
+}
+
+async fn write_records(
+    mut sink: AuditSink,
+    mut receiver: mpsc::Receiver<AuditRecord>,
+    close: CancellationToken,
+) {
+    let mut batch = Vec::with_capacity(MAX_BATCH);
+    let mut closing = false;
+
+    loop {
+        tokio::select! {
+            _ = close.cancelled(), if !closing => {
+                // Buffered records are still received, until the channel is empty.
+                receiver.close();
+                closing = true;
+            }
+            received = receiver.recv_many(&mut batch, MAX_BATCH) => {
+                if received == 0 {
+                    break;
+                }
+
+                if let Err(error) = sink.write(&batch).await {
+                    prometheus::record_audit_failure(sink.as_ref(), batch.len());
+                    tracing::error!(
+                        sink = sink.as_ref(),
+                        records = batch.len(),
+                        error = ?error,
+                        "Failed to write audit records"
+                    );
+                }
+
+                batch.clear();
+            }
+        }
+    }
+}
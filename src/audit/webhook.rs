@@ -0,0 +1,68 @@
+use crate::audit::AuditRecord;
+use crate::config::WebhookAuditSinkConfig;
+use anyhow::Context;
+use reqwest::Client;
+use reqwest::header::CONTENT_TYPE;
+use std::time::Duration;
+
+/// Attempts per batch before it is given up on.
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub struct WebhookSink {
+    client: Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl WebhookSink {
+    pub fn new(config: &WebhookAuditSinkConfig) -> anyhow::Result<Self> {
+        let client = Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .context("Failed to create webhook client")?;
+
+        Ok(Self {
+            client,
+            url: config.url.clone(),
+            token: config.token.clone(),
+        })
+    }
+
+    /// Posts the records as JSON lines, retrying failed requests with a linear backoff.
+    pub async fn write(&mut self, records: &[AuditRecord]) -> anyhow::Result<()> {
+        let mut body = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut body, record).context("Failed to serialize record")?;
+            body.push(b'\n');
+        }
+
+        let mut attempt = 1;
+        loop {
+            match self.send(body.clone()).await {
+                Ok(()) => return Ok(()),
+                Err(error) if attempt < MAX_ATTEMPTS => {
+                    tracing::warn!(attempt, error = ?error, "Failed to deliver audit records");
+                    tokio::time::sleep(RETRY_DELAY * attempt).await;
+                    attempt += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    async fn send(&self, body: Vec<u8>) -> anyhow::Result<()> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .body(body);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
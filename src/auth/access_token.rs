@@ -0,0 +1,156 @@
+use crate::data::release_data::{AccessToken, TokenScope};
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use dashmap::DashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+use std::sync::{LazyLock, Mutex};
+use std::thread::available_parallelism;
+use subtle::ConstantTimeEq;
+use tokio::sync::Semaphore;
+use tokio::task;
+
+const SHA256_PREFIX: &str = "sha256:";
+const ARGON2_PREFIX: &str = "$argon2";
+
+/// Checks a token against an access token from `project.json`, stored either as
+/// an argon2 PHC string, as `sha256:<salt>:<hex digest of salt + token>` or in plaintext.
+pub fn verify(stored: &str, token: &str) -> bool {
+    if stored.starts_with(ARGON2_PREFIX) {
+        return PasswordHash::new(stored).is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(token.as_bytes(), &hash)
+                .is_ok()
+        });
+    }
+
+    if let Some(salted) = stored.strip_prefix(SHA256_PREFIX) {
+        let Some((salt, digest)) = salted.split_once(':') else {
+            return false;
+        };
+
+        let computed = hex(&Sha256::new()
+            .chain_update(salt)
+            .chain_update(token)
+            .finalize());
+        return computed
+            .as_bytes()
+            .ct_eq(digest.to_ascii_lowercase().as_bytes())
+            .into();
+    }
+
+    stored.as_bytes().ct_eq(token.as_bytes()).into()
+}
+
+/// Key under which a successfully verified token can be cached without keeping it in memory.
+pub fn fingerprint(token: &str) -> Vec<u8> {
+    Sha256::digest(token).to_vec()
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Argon2 takes tens of milliseconds per stored hash. Verifications are capped at one per CPU,
+/// so a flood of guessed tokens cannot take over the blocking pool.
+static ARGON2_PERMITS: LazyLock<Semaphore> =
+    LazyLock::new(|| Semaphore::new(available_parallelism().map_or(1, usize::from)));
+
+/// Access tokens of a release, remembering tokens that verified or were rejected so hashes are
+/// not checked again for the same token.
+#[derive(Debug, Default)]
+pub struct AccessTokens {
+    entries: Vec<AccessToken>,
+    /// Scopes of tokens that matched by fingerprint
+    verified: DashMap<Vec<u8>, TokenScope>,
+    /// Fingerprints of recently rejected tokens
+    rejected: Mutex<RejectedTokens>,
+}
+
+impl AccessTokens {
+    pub fn new(entries: Vec<AccessToken>) -> Self {
+        Self {
+            entries,
+            ..Default::default()
+        }
+    }
+
+    /// Scope of the first entry matching the token, None when none does.
+    pub async fn scope(&self, token: &str) -> Option<TokenScope> {
+        if token.is_empty() {
+            return None;
+        }
+
+        let fingerprint = fingerprint(token);
+        if let Some(scope) = self.verified.get(&fingerprint) {
+            return Some(scope.clone());
+        }
+
+        if self.rejected.lock().unwrap().contains(&fingerprint) {
+            return None;
+        }
+
+        let scope = self.verify(token).await;
+        match &scope {
+            Some(scope) => {
+                self.verified.insert(fingerprint, scope.clone());
+            }
+            None => self.rejected.lock().unwrap().insert(fingerprint),
+        }
+
+        scope
+    }
+
+    async fn verify(&self, token: &str) -> Option<TokenScope> {
+        let find = |entries: &[AccessToken], token: &str| {
+            entries
+                .iter()
+                .find(|at| verify(at.token(), token))
+                .map(AccessToken::scope)
+        };
+
+        if !self
+            .entries
+            .iter()
+            .any(|at| at.token().starts_with(ARGON2_PREFIX))
+        {
+            return find(&self.entries, token);
+        }
+
+        // Too slow to hold a runtime worker
+        let _permit = ARGON2_PERMITS.acquire().await.ok()?;
+        let entries = self.entries.clone();
+        let token = token.to_string();
+        task::spawn_blocking(move || find(&entries, &token))
+            .await
+            .ok()
+            .flatten()
+    }
+}
+
+/// Bounded set of fingerprints, evicting the oldest once full.
+#[derive(Default, Debug)]
+struct RejectedTokens {
+    fingerprints: HashSet<Vec<u8>>,
+    order: VecDeque<Vec<u8>>,
+}
+
+impl RejectedTokens {
+    const CAPACITY: usize = 1024;
+
+    fn contains(&self, fingerprint: &[u8]) -> bool {
+        self.fingerprints.contains(fingerprint)
+    }
+
+    fn insert(&mut self, fingerprint: Vec<u8>) {
+        if !self.fingerprints.insert(fingerprint.clone()) {
+            return;
+        }
+
+        self.order.push_back(fingerprint);
+        if self.order.len() > Self::CAPACITY
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.fingerprints.remove(&oldest);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use crate::Project;
+use crate::auth::access_token::AccessTokens;
+use crate::engine_ext::EngineExtension;
+use crate::tls::ClientIdentity;
+use crate::util::glob::glob_match;
+use itertools::Itertools;
+use std::sync::Arc;
+
+/// Credentials of every served secured project, so metadata routes can authenticate a caller
+/// without checking each project. Each stored token is verified at most once per request.
+#[derive(Debug, Default)]
+pub struct Credentials {
+    access_tokens: AccessTokens,
+    client_identities: Vec<Arc<str>>,
+}
+
+impl Credentials {
+    pub fn new<'a>(projects: impl Iterator<Item = &'a Arc<Project>>) -> Self {
+        let release_data = projects
+            .filter_map(|p| p.engine.release_data())
+            .collect::<Vec<_>>();
+
+        let access_tokens = release_data
+            .iter()
+            .flat_map(|rd| rd.access_tokens.iter().cloned())
+            .unique_by(|at| at.token().to_string())
+            .collect();
+        let client_identities = release_data
+            .iter()
+            .flat_map(|rd| rd.client_identities.iter().cloned())
+            .unique()
+            .collect();
+
+        Self {
+            access_tokens: AccessTokens::new(access_tokens),
+            client_identities,
+        }
+    }
+
+    /// Whether the token is an access token of any secured project.
+    pub async fn verify_token(&self, token: &str) -> bool {
+        self.access_tokens.scope(token).await.is_some()
+    }
+
+    /// Whether a secured project grants access to the client certificate.
+    pub fn allows_client(&self, identity: &ClientIdentity) -> bool {
+        self.client_identities.iter().any(|pattern| {
+            identity
+                .names()
+                .iter()
+                .any(|name| glob_match(pattern, name))
+        })
+    }
+}
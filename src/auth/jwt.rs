@@ -0,0 +1,186 @@
+use crate::Project;
+use crate::config::JwtConfig;
+use crate::data::release_data::TokenScope;
+use crate::engine_ext::EngineExtension;
+use anyhow::{Context, bail};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use tokio::time;
+
+#[derive(Debug)]
+pub struct JwtAuthenticator {
+    config: JwtConfig,
+    source: JwksSource,
+    keys: RwLock<JwkSet>,
+}
+
+#[derive(Debug)]
+enum JwksSource {
+    File(PathBuf),
+    Url {
+        url: String,
+        client: reqwest::Client,
+    },
+}
+
+impl JwtAuthenticator {
+    pub async fn new(config: JwtConfig) -> anyhow::Result<Arc<Self>> {
+        let source = match (&config.jwks_file, &config.jwks_url) {
+            (Some(path), None) => JwksSource::File(path.clone()),
+            (None, Some(url)) => JwksSource::Url {
+                url: url.clone(),
+                client: reqwest::Client::new(),
+            },
+            _ => bail!("Exactly one of JWT__JWKS_FILE and JWT__JWKS_URL must be set"),
+        };
+
+        let authenticator = Arc::new(Self {
+            config,
+            source,
+            keys: RwLock::new(JwkSet { keys: Vec::new() }),
+        });
+
+        // An unreachable identity provider should not keep the agent from serving static tokens.
+        if let Err(error) = authenticator.refresh_keys().await {
+            tracing::error!(
+                "Failed to load JWKS, bearer tokens are rejected until it loads: {error:?}"
+            );
+        }
+
+        authenticator.register_refresh_keys();
+        Ok(authenticator)
+    }
+
+    async fn refresh_keys(&self) -> anyhow::Result<()> {
+        let content = match &self.source {
+            JwksSource::File(path) => tokio::fs::read(path)
+                .await
+                .with_context(|| format!("Failed to read JWKS file {}", path.display()))?,
+            JwksSource::Url { url, client } => client
+                .get(url)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .with_context(|| format!("Failed to fetch JWKS from {url}"))?
+                .bytes()
+                .await?
+                .to_vec(),
+        };
+
+        let keys: JwkSet = serde_json::from_slice(&content).context("Invalid JWKS document")?;
+        *self.keys.write().expect("JWKS lock is poisoned") = keys;
+
+        Ok(())
+    }
+
+    fn register_refresh_keys(self: &Arc<Self>) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(this.config.jwks_refresh_interval);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                if let Err(error) = this.refresh_keys().await {
+                    tracing::warn!("Failed to refresh JWKS: {error:?}");
+                }
+            }
+        });
+    }
+
+    pub fn authenticate(&self, token: &str) -> anyhow::Result<JwtGrant> {
+        let header = decode_header(token).context("Malformed bearer token")?;
+
+        let keys = self.keys.read().expect("JWKS lock is poisoned");
+        let jwk = match &header.kid {
+            Some(kid) => keys.find(kid),
+            None if keys.keys.len() == 1 => keys.keys.first(),
+            None => None,
+        }
+        .context("No matching signing key")?;
+
+        if let Some(key_algorithm) = jwk.common.key_algorithm
+            && Algorithm::from_str(&key_algorithm.to_string()).ok() != Some(header.alg)
+        {
+            bail!("Token algorithm does not match the signing key");
+        }
+
+        let mut validation = Validation::new(header.alg);
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(issuer) = &self.config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let key = DecodingKey::from_jwk(jwk).context("Invalid signing key")?;
+        let claims = decode::<Map<String, Value>>(token, &key, &validation)
+            .context("Invalid bearer token")?
+            .claims;
+
+        Ok(JwtGrant {
+            subject: claims.get("sub").and_then(Value::as_str).map(Arc::from),
+            projects: claim_list(&claims, &self.config.projects_claim).unwrap_or_default(),
+            decisions: claim_list(&claims, &self.config.decisions_claim)
+                .map(|decisions| decisions.into_iter().map(Arc::from).collect()),
+        })
+    }
+}
+
+/// Projects and decisions granted by a validated token.
+#[derive(Debug, Clone)]
+pub struct JwtGrant {
+    subject: Option<Arc<str>>,
+    projects: Vec<String>,
+    decisions: Option<Vec<Arc<str>>>,
+}
+
+impl JwtGrant {
+    pub fn allows_project(&self, project: &str, project_data: &Project) -> bool {
+        let release_data = project_data.engine.release_data();
+
+        self.projects.iter().any(|p| {
+            p == "*"
+                || p == project
+                || release_data
+                    .as_ref()
+                    .is_some_and(|rd| *p == *rd.project.id || *p == *rd.project.key)
+        })
+    }
+
+    pub fn subject(&self) -> Option<&Arc<str>> {
+        self.subject.as_ref()
+    }
+
+    pub fn scope(&self) -> TokenScope {
+        TokenScope {
+            decisions: self.decisions.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Accepts an array of strings or a space/comma separated string, like the `scope` claim.
+fn claim_list(claims: &Map<String, Value>, name: &str) -> Option<Vec<String>> {
+    match claims.get(name)? {
+        Value::Array(items) => Some(
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+        ),
+        Value::String(s) => Some(
+            s.split([' ', ','])
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ),
+        _ => None,
+    }
+}
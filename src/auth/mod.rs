@@ -0,0 +1,221 @@
+use crate::config::EnvironmentConfig;
+use crate::data::release_data::TokenScope;
+use crate::engine_ext::EngineExtension;
+use crate::routes::engine::EvaluateError;
+use crate::tls::ClientIdentity;
+use crate::{Agent, Project};
+use anyhow::anyhow;
+use axum::Extension;
+use axum::extract::Request;
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::sync::Arc;
+
+pub mod access_token;
+mod credentials;
+mod jwt;
+
+pub use credentials::Credentials;
+use jwt::{JwtAuthenticator, JwtGrant};
+
+/// Resolves request credentials into a [Caller].
+#[derive(Debug, Clone, Default)]
+pub struct Auth {
+    jwt: Option<Arc<JwtAuthenticator>>,
+    require_metadata_auth: bool,
+}
+
+impl Auth {
+    pub async fn new(config: &EnvironmentConfig) -> anyhow::Result<Self> {
+        let jwt = match &config.jwt {
+            Some(jwt_config) => Some(JwtAuthenticator::new(jwt_config.clone()).await?),
+            None => None,
+        };
+
+        Ok(Self {
+            jwt,
+            require_metadata_auth: config.require_metadata_auth,
+        })
+    }
+
+    /// Fails when a bearer token is present but invalid. The client identity is only present
+    /// on connections verified through mTLS.
+    pub fn caller(
+        &self,
+        headers: &HeaderMap,
+        client: Option<ClientIdentity>,
+    ) -> Result<Caller, AuthError> {
+        let access_token = headers
+            .get("X-Access-Token")
+            .map(|h| h.to_str().unwrap_or(""))
+            .unwrap_or_default()
+            .to_string();
+
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "));
+
+        // Bearer tokens are left to the gateway unless JWT validation is configured.
+        let jwt = match (&self.jwt, bearer) {
+            (Some(authenticator), Some(token)) => match authenticator.authenticate(token.trim()) {
+                Ok(grant) => Some(grant),
+                Err(error) => {
+                    tracing::debug!("Rejected bearer token: {error:?}");
+                    return Err(AuthError(format!("{error}")));
+                }
+            },
+            _ => None,
+        };
+
+        Ok(Caller {
+            access_token,
+            jwt,
+            client,
+        })
+    }
+
+    /// Caller of a metadata endpoint. With `require_metadata_auth` the caller must hold
+    /// credentials for at least one project.
+    pub async fn metadata_caller(
+        &self,
+        headers: &HeaderMap,
+        client: Option<ClientIdentity>,
+        agent: &Agent,
+    ) -> Result<Caller, AuthError> {
+        let caller = self.caller(headers, client)?;
+        self.authenticate_metadata(&caller, agent).await?;
+
+        Ok(caller)
+    }
+
+    /// With `require_metadata_auth`, fails unless the caller holds credentials for at least one
+    /// project. Callers already granted a secured project need not be checked again.
+    pub async fn authenticate_metadata(
+        &self,
+        caller: &Caller,
+        agent: &Agent,
+    ) -> Result<(), AuthError> {
+        if self.require_metadata_auth && !caller.is_authenticated(agent).await {
+            return Err(AuthError::authentication_required());
+        }
+
+        Ok(())
+    }
+
+    pub fn requires_metadata_auth(&self) -> bool {
+        self.require_metadata_auth
+    }
+}
+
+/// Guards routes outside of the handlers, such as the OpenAPI document.
+pub async fn require_metadata_auth(
+    Extension(agent): Extension<Agent>,
+    Extension(auth): Extension<Auth>,
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let client = request.extensions().get::<ClientIdentity>().cloned();
+    auth.metadata_caller(request.headers(), client, &agent)
+        .await?;
+    Ok(next.run(request).await)
+}
+
+#[derive(Debug, Clone)]
+pub struct Caller {
+    access_token: String,
+    jwt: Option<JwtGrant>,
+    client: Option<ClientIdentity>,
+}
+
+impl Caller {
+    /// Scope granted by one of the project's access tokens, its client certificate identities,
+    /// or else by a JWT listing the project. None when the caller cannot access the project.
+    pub async fn scope(&self, project: &str, project_data: &Project) -> Option<TokenScope> {
+        if let Some(scope) = project_data.engine.access_scope(&self.access_token).await {
+            return Some(scope);
+        }
+
+        if let Some(client) = &self.client
+            && let Some(scope) = project_data.engine.client_scope(client)
+        {
+            return Some(scope);
+        }
+
+        self.jwt
+            .as_ref()
+            .filter(|grant| grant.allows_project(project, project_data))
+            .map(JwtGrant::scope)
+    }
+
+    /// Who is recorded in the audit log, following the precedence of [Caller::scope]. Access tokens
+    /// are recorded by fingerprint. None for anonymous callers of public projects.
+    pub async fn identity(&self, project: &str, project_data: &Project) -> Option<Arc<str>> {
+        if project_data.engine.release_data().is_some()
+            && project_data
+                .engine
+                .access_scope(&self.access_token)
+                .await
+                .is_some()
+        {
+            let fingerprint = access_token::fingerprint(&self.access_token);
+            return Some(format!("token:{}", access_token::hex(&fingerprint)).into());
+        }
+
+        if let Some(client) = &self.client
+            && project_data.engine.client_scope(client).is_some()
+            && let Some(name) = client.names().first()
+        {
+            return Some(format!("client:{name}").into());
+        }
+
+        self.jwt
+            .as_ref()
+            .filter(|grant| grant.allows_project(project, project_data))
+            .and_then(JwtGrant::subject)
+            .map(|subject| format!("jwt:{subject}").into())
+    }
+
+    /// Holds a valid JWT, or an access token or client certificate of a project that requires one.
+    /// Checked against the credentials of all projects at once rather than project by project.
+    pub async fn is_authenticated(&self, agent: &Agent) -> bool {
+        if self.jwt.is_some() {
+            return true;
+        }
+
+        let credentials = agent.credentials();
+        if let Some(client) = &self.client
+            && credentials.allows_client(client)
+        {
+            return true;
+        }
+
+        credentials.verify_token(&self.access_token).await
+    }
+
+    pub fn has_jwt(&self) -> bool {
+        self.jwt.is_some()
+    }
+}
+
+#[derive(Debug)]
+pub struct AuthError(String);
+
+impl AuthError {
+    pub fn authentication_required() -> Self {
+        Self("Authentication required".to_string())
+    }
+}
+
+impl From<AuthError> for (StatusCode, String) {
+    fn from(value: AuthError) -> Self {
+        (StatusCode::UNAUTHORIZED, value.0)
+    }
+}
+
+impl From<AuthError> for EvaluateError {
+    fn from(value: AuthError) -> Self {
+        EvaluateError::Anyhow((StatusCode::UNAUTHORIZED, anyhow!(value.0)))
+    }
+}
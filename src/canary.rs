@@ -0,0 +1,68 @@
+use crate::Project;
+use crate::data::release_data::ReleaseDataCanary;
+use crate::engine_ext::EngineExtension;
+use axum::http::HeaderMap;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Spreads requests without a sticky value evenly across the split.
+static UNSTICKY_REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+pub struct Canary {
+    project: Arc<Project>,
+    config: ReleaseDataCanary,
+    sticky_header: Option<String>,
+}
+
+impl Canary {
+    /// Returns None when the candidate does not configure a traffic split.
+    pub fn new(project: Arc<Project>, headers: &HeaderMap) -> Option<Self> {
+        let config = project.engine.release_data()?.canary?;
+        if config.weight == 0 {
+            return None;
+        }
+
+        let sticky_header = config
+            .sticky_header
+            .as_deref()
+            .and_then(|name| headers.get(name))
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
+
+        Some(Self {
+            project,
+            config,
+            sticky_header,
+        })
+    }
+
+    /// The canary release when the request falls into its share of traffic.
+    pub fn route(&self, context: &Value) -> Option<Arc<Project>> {
+        let sticky_value = self.sticky_header.clone().or_else(|| {
+            let path = self.config.sticky_field.as_deref()?;
+            let value = path
+                .split('.')
+                .try_fold(context, |value, segment| value.get(segment))?;
+
+            match value {
+                Value::Null => None,
+                Value::String(s) => Some(s.clone()),
+                value => Some(value.to_string()),
+            }
+        });
+
+        let bucket = match sticky_value {
+            // A stable hash keeps the split identical across agents and restarts.
+            Some(value) => {
+                let digest = Sha256::digest(value.as_bytes());
+                let prefix: [u8; 8] = digest[..8].try_into().expect("digest has 32 bytes");
+                u64::from_be_bytes(prefix) % 100
+            }
+            None => UNSTICKY_REQUESTS.fetch_add(1, Ordering::Relaxed) % 100,
+        };
+
+        (bucket < u64::from(self.config.weight)).then(|| self.project.clone())
+    }
+}
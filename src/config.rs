@@ -2,8 +2,15 @@ use anyhow::Context;
 use axum_server::tls_rustls::RustlsConfig;
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
+use rustls::RootCertStore;
+use rustls::ServerConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
 use serde::de::Error;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 use strum_macros::AsRefStr;
@@ -28,12 +35,95 @@ pub struct EnvironmentConfig {
 
     #[serde(default)]
     pub http_ssl: Option<HttpSslConfig>,
+
+    /// Defaults to 127.0.0.1 in debug builds and 0.0.0.0 in release builds
+    #[serde(default)]
+    pub http_host: Option<String>,
+    /// Defaults to 3000 in debug builds and 8080 in release builds
+    #[serde(default)]
+    pub http_port: Option<u16>,
+    /// Listen on a Unix domain socket instead of TCP
+    #[serde(default)]
+    pub http_unix_socket: Option<PathBuf>,
+
+    #[serde(default)]
+    pub readiness: ReadinessConfig,
+
+    /// How long to wait for in-flight requests after a shutdown signal
+    #[serde(
+        deserialize_with = "deserialize_millis",
+        default = "default_shutdown_timeout"
+    )]
+    pub shutdown_timeout: Duration,
+
+    /// How long to keep serving after readiness starts failing, before draining
+    #[serde(deserialize_with = "deserialize_millis", default)]
+    pub shutdown_delay: Duration,
+
+    #[serde(default)]
+    pub admin: AdminConfig,
+
+    /// Number of releases kept in memory per project, available for pinning. Each one holds a
+    /// full engine, so only the served release is kept by default
+    #[serde(default = "default_release_history")]
+    pub release_history: usize,
+
+    /// Accept `Authorization: Bearer` JWTs in addition to project access tokens
+    #[serde(default)]
+    pub jwt: Option<JwtConfig>,
+
+    /// Require credentials for project listings, project metadata and the OpenAPI document
+    #[serde(default)]
+    pub require_metadata_auth: bool,
+
+    /// Default limit for project routes, overridden by `rateLimit` in a project's release data
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Longest an evaluation may run before failing with 504, `X-Evaluation-Timeout` may lower it
+    #[serde(
+        deserialize_with = "deserialize_millis",
+        default = "default_evaluation_timeout"
+    )]
+    pub evaluation_timeout: Duration,
+
+    /// Deepest nesting of decision graphs, projects may lower it with `maxDepth`
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u8,
+
+    /// Who may request evaluation traces
+    #[serde(default)]
+    pub trace_policy: TracePolicy,
+
+    /// Records every evaluation to a sink, disabled when absent
+    #[serde(default)]
+    pub audit: Option<AuditConfig>,
+
+    /// Shadow evaluations running at once, further ones are skipped. Defaults to half the workers
+    #[serde(default)]
+    pub shadow_concurrency: Option<usize>,
 }
 
 fn default_refresh_interval() -> Duration {
     Duration::from_millis(5_000)
 }
 
+fn default_max_depth() -> u8 {
+    10
+}
+
+fn default_evaluation_timeout() -> Duration {
+    Duration::from_millis(30_000)
+}
+
+fn default_shutdown_timeout() -> Duration {
+    Duration::from_millis(30_000)
+}
+
+fn default_release_history() -> usize {
+    1
+}
+
 impl Default for EnvironmentConfig {
     fn default() -> Self {
         Self {
@@ -43,10 +133,57 @@ impl Default for EnvironmentConfig {
             poll_interval: Duration::from_millis(5_000),
             otel_enabled: false,
             http_ssl: None,
+            http_host: None,
+            http_port: None,
+            http_unix_socket: None,
+            readiness: ReadinessConfig::default(),
+            shutdown_timeout: default_shutdown_timeout(),
+            shutdown_delay: Duration::ZERO,
+            admin: AdminConfig::default(),
+            release_history: default_release_history(),
+            jwt: None,
+            require_metadata_auth: false,
+            rate_limit: None,
+            evaluation_timeout: default_evaluation_timeout(),
+            max_depth: default_max_depth(),
+            trace_policy: TracePolicy::default(),
+            audit: None,
+            shadow_concurrency: None,
         }
     }
 }
 
+impl EnvironmentConfig {
+    pub fn listen_address(&self, is_development: bool) -> anyhow::Result<ListenAddress> {
+        if let Some(path) = &self.http_unix_socket {
+            return Ok(ListenAddress::Unix(path.clone()));
+        }
+
+        let (default_host, default_port) = if is_development {
+            ("127.0.0.1", 3000)
+        } else {
+            ("0.0.0.0", 8080)
+        };
+
+        let host = self.http_host.as_deref().unwrap_or(default_host);
+        let port = self.http_port.unwrap_or(default_port);
+
+        let address = (host, port)
+            .to_socket_addrs()
+            .with_context(|| format!("Invalid listen address {host}:{port}"))?
+            .next()
+            .with_context(|| format!("Listen address {host}:{port} did not resolve"))?;
+
+        Ok(ListenAddress::Tcp(address))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
 pub fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: Deserializer<'de>,
@@ -61,6 +198,224 @@ where
     Ok(Duration::from_millis(millis))
 }
 
+pub fn deserialize_millis<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let millis = <u64>::deserialize(deserializer)?;
+    Ok(Duration::from_millis(millis))
+}
+
+/// Accepts either a sequence or a comma-separated string (as set through env variables).
+pub fn deserialize_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        Sequence(Vec<String>),
+        Separated(String),
+    }
+
+    Ok(match List::deserialize(deserializer)? {
+        List::Sequence(items) => items,
+        List::Separated(s) => s
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ReadinessConfig {
+    /// Minimum number of loaded projects before reporting ready
+    #[serde(default)]
+    pub min_projects: usize,
+    /// Project slugs (or ids) that must be loaded before reporting ready
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub required_projects: Vec<String>,
+    /// Report degraded after this many consecutive failed refreshes
+    #[serde(default)]
+    pub max_refresh_failures: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AdminConfig {
+    /// Tokens accepted by the admin endpoints; the admin API is disabled when empty
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub tokens: Vec<String>,
+}
+
+/// Token bucket refilled at `rate` requests per second, holding up to `burst` requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub key: RateLimitKey,
+    /// Zero disables the limit
+    #[serde(deserialize_with = "deserialize_rate")]
+    pub rate: f64,
+    /// Defaults to `rate`, and at least one request
+    #[serde(default)]
+    pub burst: Option<u32>,
+}
+
+impl RateLimitConfig {
+    pub fn burst(&self) -> f64 {
+        self.burst.map_or(self.rate, f64::from).max(1.0)
+    }
+}
+
+/// Slowest refill accepted, slower rates would overflow the time until a bucket refills.
+const MIN_RATE: f64 = 1e-6;
+
+fn deserialize_rate<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let rate = <f64>::deserialize(deserializer)?;
+    if rate != 0.0 && !(MIN_RATE..=f64::MAX).contains(&rate) {
+        return Err(Error::custom(format!(
+            "rate must be 0 or at least {MIN_RATE} requests per second ({rate} given)"
+        )));
+    }
+
+    Ok(rate)
+}
+
+/// What requests share a bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitKey {
+    /// Access token or bearer token, falling back to the client IP for anonymous requests
+    #[default]
+    Token,
+    Ip,
+    Project,
+}
+
+/// Who may request evaluation traces, on top of the caller's token scope.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TracePolicy {
+    #[default]
+    Allow,
+    /// Only requests that also carry an admin token in `X-Admin-Token`
+    Admin,
+    Deny,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuditConfig {
+    pub sink: AuditSinkConfig,
+    /// Records waiting for the sink, further evaluations are not audited until it catches up
+    #[serde(default = "default_audit_buffer")]
+    pub buffer: usize,
+}
+
+#[derive(Debug, Clone, Deserialize, AsRefStr)]
+#[serde(tag = "type")]
+pub enum AuditSinkConfig {
+    File(FileAuditSinkConfig),
+    Webhook(WebhookAuditSinkConfig),
+}
+
+/// JSON lines file, rotated to `<path>.1` .. `<path>.<max_files>` once it reaches `max_bytes`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileAuditSinkConfig {
+    pub path: PathBuf,
+    #[serde(default = "default_audit_max_bytes")]
+    pub max_bytes: u64,
+    /// Rotated files kept next to the current one
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: usize,
+}
+
+/// Endpoint receiving batches of records as `application/x-ndjson` POST requests.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookAuditSinkConfig {
+    pub url: String,
+    /// Sent as `Authorization: Bearer <token>`
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(
+        deserialize_with = "deserialize_millis",
+        default = "default_audit_webhook_timeout"
+    )]
+    pub timeout: Duration,
+}
+
+fn default_audit_buffer() -> usize {
+    10_000
+}
+
+fn default_audit_max_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_audit_max_files() -> usize {
+    10
+}
+
+fn default_audit_webhook_timeout() -> Duration {
+    Duration::from_millis(5_000)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwtConfig {
+    /// Path to a JWKS document, exclusive with `jwks_url`
+    #[serde(default)]
+    pub jwks_file: Option<PathBuf>,
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    /// How often the JWKS is re-read to pick up rotated keys
+    #[serde(
+        deserialize_with = "deserialize_millis",
+        default = "default_jwks_refresh_interval"
+    )]
+    pub jwks_refresh_interval: Duration,
+    /// Required `iss` claim
+    #[serde(default)]
+    pub issuer: Option<String>,
+    /// Required `aud` claim
+    #[serde(default)]
+    pub audience: Option<String>,
+    /// Claim listing allowed project slugs or ids, `*` allows all projects
+    #[serde(default = "default_projects_claim")]
+    pub projects_claim: String,
+    /// Claim listing allowed decision keys; all decisions are allowed when absent
+    #[serde(default = "default_decisions_claim")]
+    pub decisions_claim: String,
+}
+
+fn default_jwks_refresh_interval() -> Duration {
+    Duration::from_millis(300_000)
+}
+
+fn default_projects_claim() -> String {
+    "projects".to_string()
+}
+
+fn default_decisions_claim() -> String {
+    "decisions".to_string()
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            jwks_file: None,
+            jwks_url: None,
+            jwks_refresh_interval: default_jwks_refresh_interval(),
+            issuer: None,
+            audience: None,
+            projects_claim: default_projects_claim(),
+            decisions_claim: default_decisions_claim(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, AsRefStr)]
 #[serde(tag = "type")]
 pub enum ProviderConfig {
@@ -138,21 +493,124 @@ pub struct GlobalAgentConfig {
     pub release_zip_password: Option<Arc<str>>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct HttpSslConfig {
-    pub key: String,
-    pub cert: String,
+    /// Base64 encoded PEM private key
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Base64 encoded PEM certificate chain
+    #[serde(default)]
+    pub cert: Option<String>,
+    /// PEM private key file, takes precedence over `key` and is reloaded when it changes
+    #[serde(default)]
+    pub key_file: Option<PathBuf>,
+    /// PEM certificate chain file, takes precedence over `cert` and is reloaded when it changes
+    #[serde(default)]
+    pub cert_file: Option<PathBuf>,
+    /// Base64 encoded PEM bundle of CAs that client certificates must chain to, enables mTLS
+    #[serde(default)]
+    pub client_ca: Option<String>,
+    /// PEM client CA bundle file, takes precedence over `client_ca`
+    #[serde(default)]
+    pub client_ca_file: Option<PathBuf>,
+    /// Also accept connections without a client certificate when a client CA is set
+    #[serde(default)]
+    pub client_auth_optional: bool,
+}
+
+/// PEM contents of the configured certificate, key and client CA bundle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SslPem {
+    cert: Vec<u8>,
+    key: Vec<u8>,
+    client_ca: Option<Vec<u8>>,
 }
 
 impl HttpSslConfig {
     pub async fn to_rustls_config(&self) -> anyhow::Result<RustlsConfig> {
-        let cert = BASE64_STANDARD
-            .decode(&self.cert)
-            .context("Failed to decode SSL certificate")?;
-        let key = BASE64_STANDARD
-            .decode(&self.key)
-            .context("Failed to decode SSL key")?;
-
-        Ok(RustlsConfig::from_pem(cert, key).await?)
+        let pem = self.load_pem().await?;
+        let config = self.server_config(&pem)?;
+
+        Ok(RustlsConfig::from_config(Arc::new(config)))
+    }
+
+    /// Whether any PEM is read from disk and may change while running.
+    pub fn has_files(&self) -> bool {
+        self.cert_file.is_some() || self.key_file.is_some() || self.client_ca_file.is_some()
+    }
+
+    pub async fn load_pem(&self) -> anyhow::Result<SslPem> {
+        let cert = read_pem("certificate", self.cert_file.as_ref(), self.cert.as_ref())
+            .await?
+            .context("SSL certificate is not configured")?;
+        let key = read_pem("key", self.key_file.as_ref(), self.key.as_ref())
+            .await?
+            .context("SSL key is not configured")?;
+        let client_ca = read_pem(
+            "client CA",
+            self.client_ca_file.as_ref(),
+            self.client_ca.as_ref(),
+        )
+        .await?;
+
+        Ok(SslPem {
+            cert,
+            key,
+            client_ca,
+        })
+    }
+
+    pub fn server_config(&self, pem: &SslPem) -> anyhow::Result<ServerConfig> {
+        let certs = CertificateDer::pem_slice_iter(&pem.cert)
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to parse SSL certificate")?;
+        let key = PrivateKeyDer::from_pem_slice(&pem.key).context("Failed to parse SSL key")?;
+
+        let builder = ServerConfig::builder();
+        let builder = match &pem.client_ca {
+            None => builder.with_no_client_auth(),
+            Some(client_ca) => {
+                let mut roots = RootCertStore::empty();
+                for ca in CertificateDer::pem_slice_iter(client_ca) {
+                    roots
+                        .add(ca.context("Failed to parse SSL client CA")?)
+                        .context("Invalid SSL client CA")?;
+                }
+
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+                let verifier = match self.client_auth_optional {
+                    true => verifier.allow_unauthenticated(),
+                    false => verifier,
+                };
+
+                builder.with_client_cert_verifier(verifier.build()?)
+            }
+        };
+
+        let mut config = builder.with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(config)
     }
 }
+
+async fn read_pem(
+    name: &str,
+    path: Option<&PathBuf>,
+    base64: Option<&String>,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    if let Some(path) = path {
+        let content = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read SSL {name} from {}", path.display()))?;
+        return Ok(Some(content));
+    }
+
+    base64
+        .map(|value| {
+            BASE64_STANDARD
+                .decode(value)
+                .with_context(|| format!("Failed to decode SSL {name}"))
+        })
+        .transpose()
+}
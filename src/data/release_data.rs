@@ -1,4 +1,6 @@
-use serde::{Deserialize, Serialize};
+use crate::config::RateLimitConfig;
+use crate::util::glob::glob_match;
+use serde::{Deserialize, Deserializer, Serialize, de};
 use std::sync::Arc;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -7,8 +9,21 @@ pub struct ReleaseData {
     pub version: Option<Arc<str>>,
     pub project: ReleaseDataProject,
     #[serde(default)]
-    pub access_tokens: Vec<Arc<str>>,
+    pub access_tokens: Vec<AccessToken>,
+    /// Glob patterns of client certificate subject common names or SANs granted access over mTLS
+    #[serde(default)]
+    pub client_identities: Vec<Arc<str>>,
     pub release: ReleaseDataRelease,
+    #[serde(default)]
+    pub canary: Option<ReleaseDataCanary>,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Deepest nesting of decision graphs, capped by the agent's `MAX_DEPTH`
+    #[serde(default)]
+    pub max_depth: Option<u8>,
+    /// Glob patterns of dot separated trace paths whose values are redacted from responses
+    #[serde(default)]
+    pub trace_redaction: Vec<Arc<str>>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -24,3 +39,96 @@ pub struct ReleaseDataRelease {
     pub id: Arc<str>,
     pub version: Arc<str>,
 }
+
+/// Traffic split applied while this release is loaded under the `{project}.canary` key.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseDataCanary {
+    /// Percentage of evaluations answered by this release
+    #[serde(deserialize_with = "deserialize_weight")]
+    pub weight: u8,
+    /// Dot separated context path hashed to keep routing sticky
+    #[serde(default)]
+    pub sticky_field: Option<Arc<str>>,
+    /// Request header hashed to keep routing sticky, takes precedence over `stickyField`
+    #[serde(default)]
+    pub sticky_header: Option<Arc<str>>,
+}
+
+fn deserialize_weight<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let weight = <u8>::deserialize(deserializer)?;
+    if weight > 100 {
+        return Err(de::Error::custom(format!(
+            "canary weight is a percentage, got {weight}"
+        )));
+    }
+
+    Ok(weight)
+}
+
+/// Either a bare token with full access or a token restricted to a scope.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AccessToken {
+    Plain(Arc<str>),
+    Scoped {
+        token: Arc<str>,
+        #[serde(flatten)]
+        scope: TokenScope,
+    },
+}
+
+impl AccessToken {
+    pub fn token(&self) -> &str {
+        match self {
+            AccessToken::Plain(token) => token,
+            AccessToken::Scoped { token, .. } => token,
+        }
+    }
+
+    pub fn scope(&self) -> TokenScope {
+        match self {
+            AccessToken::Plain(_) => TokenScope::default(),
+            AccessToken::Scoped { scope, .. } => scope.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenScope {
+    /// Glob patterns of decision keys that may be evaluated, all decisions when absent
+    #[serde(default)]
+    pub decisions: Option<Vec<Arc<str>>>,
+    /// Whether the project entrypoints may be listed
+    #[serde(default = "default_true")]
+    pub entrypoints: bool,
+    /// Whether evaluations may request a trace
+    #[serde(default = "default_true")]
+    pub trace: bool,
+}
+
+impl Default for TokenScope {
+    fn default() -> Self {
+        Self {
+            decisions: None,
+            entrypoints: true,
+            trace: true,
+        }
+    }
+}
+
+impl TokenScope {
+    pub fn allows_decision(&self, key: &str) -> bool {
+        self.decisions
+            .as_ref()
+            .is_none_or(|patterns| patterns.iter().any(|p| glob_match(p, key)))
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
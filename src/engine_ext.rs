@@ -1,5 +1,6 @@
-use crate::data::release_data::ReleaseData;
+use crate::data::release_data::{ReleaseData, TokenScope};
 use crate::immutable_loader::ImmutableLoader;
+use crate::tls::ClientIdentity;
 use std::sync::Arc;
 use zen_engine::DecisionEngine;
 
@@ -7,8 +8,10 @@ pub trait EngineExtension {
     fn release_data(&self) -> Option<ReleaseData>;
     fn get_version(&self, path: &str) -> Option<Arc<str>>;
 
-    fn can_access(&self, token: &str) -> bool;
+    fn access_scope(&self, token: &str) -> impl Future<Output = Option<TokenScope>> + Send;
+    fn client_scope(&self, identity: &ClientIdentity) -> Option<TokenScope>;
     fn decision_keys(&self) -> Vec<String>;
+    fn has_decision(&self, key: &str) -> bool;
 }
 
 impl EngineExtension for DecisionEngine {
@@ -27,11 +30,18 @@ impl EngineExtension for DecisionEngine {
             .get_version(path)
     }
 
-    fn can_access(&self, token: &str) -> bool {
+    fn access_scope(&self, token: &str) -> impl Future<Output = Option<TokenScope>> + Send {
+        let loader = self.loader().downcast_arc::<ImmutableLoader>().ok();
+        let token = token.to_string();
+
+        async move { loader?.access_scope(&token).await }
+    }
+
+    fn client_scope(&self, identity: &ClientIdentity) -> Option<TokenScope> {
         self.loader()
             .downcast_arc::<ImmutableLoader>()
-            .ok()
-            .map_or(false, |loader| loader.can_access(token))
+            .ok()?
+            .client_scope(identity)
     }
 
     fn decision_keys(&self) -> Vec<String> {
@@ -40,4 +50,11 @@ impl EngineExtension for DecisionEngine {
             .ok()
             .map_or_else(Vec::new, |loader| loader.decision_keys())
     }
+
+    fn has_decision(&self, key: &str) -> bool {
+        self.loader()
+            .downcast_arc::<ImmutableLoader>()
+            .ok()
+            .is_some_and(|loader| loader.has_decision(key))
+    }
 }
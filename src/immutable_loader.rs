@@ -8,9 +8,12 @@ use std::path::Component;
 use std::pin::Pin;
 use std::sync::Arc;
 
+use crate::auth::access_token::AccessTokens;
 use crate::data::extended_decision::{FileContent, FileDecisionGraph};
-use crate::data::release_data::ReleaseData;
-use anyhow::anyhow;
+use crate::data::release_data::{ReleaseData, TokenScope};
+use crate::tls::ClientIdentity;
+use crate::util::glob::glob_match;
+use anyhow::{Context, anyhow};
 use zen_engine::DecisionEngine;
 use zen_engine::loader::{DecisionLoader, LoaderError, LoaderResponse};
 use zip::ZipArchive;
@@ -21,6 +24,7 @@ use zip::result::ZipResult;
 pub struct ImmutableLoader {
     release_data: Option<ReleaseData>,
     content: HashMap<String, FileDecisionGraph>,
+    access_tokens: AccessTokens,
 }
 
 impl ImmutableLoader {
@@ -28,9 +32,15 @@ impl ImmutableLoader {
         content: HashMap<String, FileDecisionGraph>,
         release_data: Option<ReleaseData>,
     ) -> Self {
+        let access_tokens = release_data
+            .as_ref()
+            .map(|rd| AccessTokens::new(rd.access_tokens.clone()))
+            .unwrap_or_default();
+
         Self {
             content,
             release_data,
+            access_tokens,
         }
     }
 
@@ -50,10 +60,30 @@ impl ImmutableLoader {
         self.content.keys().cloned().collect()
     }
 
-    pub fn can_access(&self, token: &str) -> bool {
-        self.release_data()
-            .map(|rd| rd.access_tokens.iter().any(|at| at.deref().eq(token)))
-            .unwrap_or(true)
+    pub fn has_decision(&self, key: &str) -> bool {
+        self.content.contains_key(key.to_lowercase().as_str())
+    }
+
+    /// Scope granted to the token, None when it has no access to the project.
+    pub async fn access_scope(&self, token: &str) -> Option<TokenScope> {
+        if self.release_data.is_none() {
+            return Some(TokenScope::default());
+        }
+
+        self.access_tokens.scope(token).await
+    }
+
+    /// Scope granted to a verified client certificate, None when none of its names are listed.
+    pub fn client_scope(&self, identity: &ClientIdentity) -> Option<TokenScope> {
+        let release_data = self.release_data()?;
+        let matches = release_data.client_identities.iter().any(|pattern| {
+            identity
+                .names()
+                .iter()
+                .any(|name| glob_match(pattern, name))
+        });
+
+        matches.then(TokenScope::default)
     }
 }
 
@@ -122,18 +152,20 @@ where
     fn try_from(mut archive: ProtectedZipArchive<R>) -> Result<Self, Self::Error> {
         let config_prefix = ".config";
 
-        let release_data = archive
-            .index_for_name(".config/project.json")
-            .map(|index| archive.by_index_try_decrypt(index).ok())
-            .flatten()
-            .map(|f| {
-                if !f.is_file() {
-                    return None;
-                }
-
-                serde_json::from_reader::<_, ReleaseData>(f).ok()
-            })
-            .flatten();
+        // Release data carries the access tokens, so a release whose data cannot be read must
+        // fail to load rather than be served without them.
+        let release_data = match archive.index_for_name(".config/project.json") {
+            None => None,
+            Some(index) => {
+                let file = archive
+                    .by_index_try_decrypt(index)
+                    .context("failed to open .config/project.json")?;
+                let release_data = serde_json::from_reader::<_, ReleaseData>(file)
+                    .context("failed to parse .config/project.json")?;
+
+                Some(release_data)
+            }
+        };
 
         let contents = (0..archive.len())
             .filter_map(move |i| {
@@ -1,12 +1,20 @@
 pub mod app;
+mod audit;
+mod auth;
+mod canary;
 pub mod config;
 mod data;
 mod engine_ext;
 mod immutable_loader;
+mod prometheus;
 mod provider;
+mod rate_limit;
 mod routes;
+mod shadow;
 pub mod telemetry;
+pub mod tls;
 mod util;
 
 pub use provider::Agent;
 pub use provider::Project;
+pub use provider::RefreshScope;
@@ -1,15 +1,27 @@
-use agent::config::{EnvironmentConfig, GlobalAgentConfig};
-use agent::{app, telemetry};
+use agent::config::{EnvironmentConfig, GlobalAgentConfig, ListenAddress};
+use agent::tls::{self, ClientCertAcceptor};
+use agent::{Agent, app, telemetry};
+use axum_server::{Address, Handle};
 use config::{Config, Environment};
+use std::fs;
 use std::net::SocketAddr;
-use std::str::FromStr;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::SocketAddr as UnixSocketAddr;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::signal;
+use zen_engine::ZEN_CONFIG;
 
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 const IS_DEVELOPMENT: bool = cfg!(debug_assertions);
 
+/// Lets an evaluation time out with `504` before the engine interrupts its function nodes.
+const FUNCTION_TIMEOUT_GRACE: Duration = Duration::from_millis(250);
+
 #[tokio::main]
 async fn main() {
     dotenvy::dotenv().ok();
@@ -27,13 +39,23 @@ async fn main() {
 
     telemetry::setup(cfg.otel_enabled).ok();
 
+    // Function nodes only yield to the engine's own interrupt, which is the only way to free a
+    // worker from an evaluation that timed out. The engine has a single process wide limit.
+    let function_timeout = cfg.evaluation_timeout + FUNCTION_TIMEOUT_GRACE;
+    ZEN_CONFIG.function_timeout_millis.store(
+        u64::try_from(function_timeout.as_millis()).unwrap_or(u64::MAX),
+        Ordering::Relaxed,
+    );
+
     let rustls_config = match &cfg.http_ssl {
         None => None,
         Some(s) => {
             rustls::crypto::aws_lc_rs::default_provider()
                 .install_default()
                 .expect("Failed to install rustls crypto provider");
-            Some(s.to_rustls_config().await.expect("Valid SSL Config"))
+            let rustls_config = s.to_rustls_config().await.expect("Valid SSL Config");
+            tls::reload_certificates(s.clone(), rustls_config.clone(), cfg.poll_interval);
+            Some(rustls_config)
         }
     };
 
@@ -41,31 +63,125 @@ async fn main() {
         release_zip_password: cfg.release_zip_password.clone(),
     });
 
+    let listen_address = cfg
+        .listen_address(IS_DEVELOPMENT)
+        .expect("Valid listen address");
+
+    let shutdown = Shutdown {
+        timeout: cfg.shutdown_timeout,
+        delay: cfg.shutdown_delay,
+    };
+
     let agent = app::create_agent(cfg.clone(), global_config).await;
-    let app = app::create_app(agent, cfg).await;
-
-    let listener_address_str = IS_DEVELOPMENT
-        .then_some("127.0.0.1:3000")
-        .unwrap_or("0.0.0.0:8080");
-    let listener_address =
-        SocketAddr::from_str(listener_address_str).expect("Valid socket address");
-
-    let server_result = match rustls_config {
-        None => {
-            tracing::info!("🚀 Listening on http://{listener_address}");
-            axum_server::bind(listener_address)
-                .serve(app.into_make_service())
+    let app = app::create_app(agent.clone(), cfg).await;
+
+    let server_result = match (listen_address, rustls_config) {
+        (ListenAddress::Tcp(address), None) => {
+            tracing::info!("🚀 Listening on http://{address}");
+            axum_server::bind(address)
+                .handle(shutdown.handle(agent.clone()))
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
                 .await
         }
-        Some(rustls_config) => {
-            tracing::info!("🚀 Listening on https://{listener_address}");
-            axum_server::bind_rustls(listener_address, rustls_config)
-                .serve(app.into_make_service())
+        (ListenAddress::Tcp(address), Some(rustls_config)) => {
+            tracing::info!("🚀 Listening on https://{address}");
+            axum_server::bind(address)
+                .acceptor(ClientCertAcceptor::new(rustls_config))
+                .handle(shutdown.handle(agent.clone()))
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
                 .await
         }
+        (ListenAddress::Unix(path), rustls_config) => {
+            remove_stale_socket(&path);
+            let address = UnixSocketAddr::from_pathname(&path).expect("Valid unix socket path");
+
+            match rustls_config {
+                None => {
+                    tracing::info!("🚀 Listening on unix:{}", path.display());
+                    axum_server::bind(address)
+                        .handle(shutdown.handle(agent.clone()))
+                        .serve(app.into_make_service())
+                        .await
+                }
+                Some(rustls_config) => {
+                    tracing::info!("🚀 Listening on unix:{} (TLS)", path.display());
+                    axum_server::bind(address)
+                        .acceptor(ClientCertAcceptor::new(rustls_config))
+                        .handle(shutdown.handle(agent.clone()))
+                        .serve(app.into_make_service())
+                        .await
+                }
+            }
+        }
     };
 
     if let Err(error) = server_result {
         tracing::error!("Server exited with an error: {error:?}");
     }
+
+    agent.close_audit(shutdown.timeout).await;
+
+    tracing::info!("Server stopped");
+}
+
+struct Shutdown {
+    timeout: Duration,
+    delay: Duration,
+}
+
+impl Shutdown {
+    /// Creates a server handle that drains connections once a shutdown signal arrives.
+    fn handle<A: Address + Send + Sync + 'static>(&self, agent: Agent) -> Handle<A> {
+        let handle = Handle::new();
+        let (timeout, delay) = (self.timeout, self.delay);
+
+        let server_handle = handle.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+
+            tracing::info!(
+                shutdown.timeout = ?timeout,
+                shutdown.delay = ?delay,
+                connections = server_handle.connection_count(),
+                "Shutdown signal received"
+            );
+            agent.shutdown();
+
+            tokio::time::sleep(delay).await;
+            server_handle.graceful_shutdown(Some(timeout));
+        });
+
+        handle
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Binding fails if a socket from a previous run is still on disk.
+fn remove_stale_socket(path: &Path) {
+    let is_socket = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket());
+    if is_socket && let Err(error) = fs::remove_file(path) {
+        tracing::warn!(
+            "Failed to remove stale unix socket {}: {error}",
+            path.display()
+        );
+    }
 }
@@ -0,0 +1,111 @@
+use crate::provider::ProjectDiff;
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const DURATION_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Installs the global Prometheus recorder on first use.
+pub fn handle() -> &'static PrometheusHandle {
+    HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Suffix("duration_seconds".to_string()),
+                DURATION_BUCKETS,
+            )
+            .expect("Valid histogram buckets")
+            .install_recorder()
+            .expect("Failed to install Prometheus recorder")
+    })
+}
+
+pub fn render() -> String {
+    let handle = handle();
+    handle.run_upkeep();
+    handle.render()
+}
+
+pub struct EvaluationLabels {
+    pub project: String,
+    pub decision: String,
+    pub release_id: String,
+}
+
+impl EvaluationLabels {
+    fn labels(&self) -> [(&'static str, String); 3] {
+        [
+            ("project", self.project.clone()),
+            ("decision", self.decision.clone()),
+            ("release_id", self.release_id.clone()),
+        ]
+    }
+}
+
+pub fn record_evaluation(labels: &EvaluationLabels, duration: Duration, success: bool) {
+    let labels = labels.labels();
+
+    counter!("agent_evaluations_total", &labels).increment(1);
+    histogram!("agent_evaluation_duration_seconds", &labels).record(duration.as_secs_f64());
+    if !success {
+        counter!("agent_evaluation_errors_total", &labels).increment(1);
+    }
+}
+
+pub fn record_shadow_evaluation(project: &str, decision: &str, outcome: &'static str) {
+    counter!(
+        "agent_shadow_evaluations_total",
+        "project" => project.to_string(),
+        "decision" => decision.to_string(),
+        "outcome" => outcome
+    )
+    .increment(1);
+}
+
+pub fn record_rate_limited(project: &str) {
+    counter!("agent_rate_limited_total", "project" => project.to_string()).increment(1);
+}
+
+pub fn record_audit_dropped() {
+    counter!("agent_audit_dropped_total").increment(1);
+}
+
+pub fn record_audit_failure(sink: &str, records: usize) {
+    counter!("agent_audit_failed_records_total", "sink" => sink.to_string())
+        .increment(records as u64);
+}
+
+pub fn record_refresh(
+    result: &anyhow::Result<Vec<ProjectDiff>>,
+    duration: Duration,
+    loaded_projects: usize,
+    failed_projects: usize,
+) {
+    histogram!("agent_refresh_duration_seconds").record(duration.as_secs_f64());
+    gauge!("agent_projects_loaded").set(loaded_projects as f64);
+    gauge!("agent_failed_projects").set(failed_projects as f64);
+
+    let diff = match result {
+        Ok(diff) => diff,
+        Err(_) => {
+            counter!("agent_refresh_failures_total").increment(1);
+            return;
+        }
+    };
+
+    let (mut created, mut updated, mut removed) = (0, 0, 0);
+    diff.iter().for_each(|d| match d {
+        ProjectDiff::Created(_) => created += 1,
+        ProjectDiff::Updated(_) => updated += 1,
+        ProjectDiff::Removed(_) => removed += 1,
+    });
+
+    counter!("agent_refresh_total").increment(1);
+    counter!("agent_refresh_projects_total", "change" => "created").increment(created);
+    counter!("agent_refresh_projects_total", "change" => "updated").increment(updated);
+    counter!("agent_refresh_projects_total", "change" => "removed").increment(removed);
+}
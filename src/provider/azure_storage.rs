@@ -3,12 +3,13 @@ use crate::config::{AzureStorageProviderConfig, GlobalAgentConfig};
 use crate::immutable_loader::{ImmutableLoader, ProtectedZipArchive};
 use crate::provider::{
     AgentData, AgentDataProvider, FailedProjectsRegistry, Project, ProjectData, ProjectDiff,
+    RefreshScope,
 };
 use crate::util::prefix::Prefix;
 use anyhow::Context;
 use azure_core::prelude::MaxResults;
 use azure_identity::{DefaultAzureCredential, TokenCredentialOptions};
-use azure_storage::{ConnectionString, StorageCredentials};
+use azure_storage::{CloudLocation, ConnectionString, StorageCredentials};
 use azure_storage_blobs::blob::BlobProperties;
 use azure_storage_blobs::container::operations::BlobItem;
 use azure_storage_blobs::prelude::{BlobServiceClient, ContainerClient};
@@ -182,7 +183,7 @@ impl AzureStorageProvider {
         config: &AzureStorageProviderConfig,
         global_config: Arc<GlobalAgentConfig>,
     ) -> anyhow::Result<Self> {
-        let (account_name, credentials) = match resolve_auth(config)? {
+        let (account_name, credentials, blob_endpoint) = match resolve_auth(config)? {
             AzureAuth::ConnectionString(raw) => {
                 let connection_string =
                     ConnectionString::new(&raw).context("Invalid connection string")?;
@@ -193,7 +194,8 @@ impl AzureStorageProvider {
                     .account_name
                     .context("Invalid account name")?
                     .to_string();
-                (account_name, credentials)
+                let blob_endpoint = connection_string.blob_endpoint.map(str::to_string);
+                (account_name, credentials, blob_endpoint)
             }
             AzureAuth::Iam { account_name } => {
                 let credential = DefaultAzureCredential::create(TokenCredentialOptions::default())
@@ -201,11 +203,21 @@ impl AzureStorageProvider {
                 (
                     account_name,
                     StorageCredentials::token_credential(Arc::new(credential)),
+                    None,
                 )
             }
         };
 
-        let blob_service = BlobServiceClient::new(account_name, credentials);
+        // Custom endpoints (e.g. Azurite) come from BlobEndpoint in the connection string.
+        let blob_service = match blob_endpoint {
+            Some(uri) => BlobServiceClient::builder(account_name.clone(), credentials)
+                .cloud_location(CloudLocation::Custom {
+                    account: account_name,
+                    uri,
+                })
+                .blob_service_client(),
+            None => BlobServiceClient::new(account_name, credentials),
+        };
 
         let container_client = blob_service.container_client(&config.container);
 
@@ -288,13 +300,7 @@ impl AzureStorageProvider {
                         }
                     };
 
-                    Some((
-                        key,
-                        Arc::new(Project {
-                            engine,
-                            content_hash,
-                        }),
-                    ))
+                    Some((key, Arc::new(Project::new(engine, content_hash))))
                 }
             })
             .buffered(100)
@@ -310,6 +316,7 @@ impl AgentDataProvider for AzureStorageProvider {
     fn load_data(
         &self,
         data: Arc<AgentData>,
+        scope: RefreshScope,
     ) -> impl Future<Output = anyhow::Result<Vec<ProjectDiff>>> + Send + 'static {
         let this = self.clone();
 
@@ -334,26 +341,18 @@ impl AgentDataProvider for AzureStorageProvider {
                     )?
                     .blobs
                     .items;
-                let blobs = items
-                    .iter()
-                    .filter_map(|blob_item| match blob_item {
-                        BlobItem::Blob(blob) => Some(ProjectData {
-                            key: this.prefix.strip(blob.name.as_str().into()).into_owned(),
-                            content_hash: extract_hash(&blob.properties),
-                        }),
-                        BlobItem::BlobPrefix(_) => None,
-                    })
-                    .filter_map(|proj_data| {
-                        if FailedProjectsRegistry::has_failed(proj_data.content_hash.as_deref()) {
-                            return None;
-                        }
-                        Some(proj_data)
-                    });
+                let blobs = items.iter().filter_map(|blob_item| match blob_item {
+                    BlobItem::Blob(blob) => Some(ProjectData {
+                        key: this.prefix.strip(blob.name.as_str().into()).into_owned(),
+                        content_hash: extract_hash(&blob.properties),
+                    }),
+                    BlobItem::BlobPrefix(_) => None,
+                });
 
                 project_datum.extend(blobs);
             }
 
-            let diff = data.calculate_diff(project_datum);
+            let diff = data.calculate_diff(project_datum, &scope);
 
             let to_refresh = Agent::get_refresh_list(&diff);
 
@@ -1,24 +1,35 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fs::File;
 use std::future::Future;
-use std::path::PathBuf;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::{env, fs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{env, fs, io};
 
+use crate::Agent;
 use crate::config::{FilesystemProviderConfig, GlobalAgentConfig};
 use crate::data::extended_decision::{FileContent, FileDecisionGraph};
 use crate::data::release_data::ReleaseData;
 use crate::immutable_loader::ImmutableLoader;
-use crate::provider::{AgentData, AgentDataProvider, Project, ProjectDiff};
-use anyhow::Context;
+use crate::provider::{
+    AgentData, AgentDataProvider, FailedProjectsRegistry, FileStamp, HashCache, Project,
+    ProjectData, ProjectDiff, RefreshScope,
+};
+use anyhow::{Context, bail};
 use dashmap::DashMap;
 use itertools::Itertools;
+use sha2::{Digest, Sha256};
 use tokio::task;
-use walkdir::WalkDir;
+use walkdir::{DirEntry, WalkDir};
 
 #[derive(Debug)]
 pub struct FilesystemProvider {
     root_dir: PathBuf,
+    /// Set once the initial load ran, after which a missing root fails the refresh
+    loaded: AtomicBool,
+    hashes: Arc<HashCache>,
 }
 
 impl FilesystemProvider {
@@ -28,7 +39,11 @@ impl FilesystemProvider {
             .join(config.root_dir.as_str())
             .to_path_buf();
 
-        Self { root_dir: root }
+        Self {
+            root_dir: root,
+            loaded: AtomicBool::new(false),
+            hashes: Default::default(),
+        }
     }
 }
 
@@ -36,100 +51,193 @@ impl AgentDataProvider for FilesystemProvider {
     fn load_data(
         &self,
         data: Arc<AgentData>,
+        scope: RefreshScope,
     ) -> impl Future<Output = anyhow::Result<Vec<ProjectDiff>>> + Send + 'static {
         let root = self.root_dir.clone();
+        let hashes = self.hashes.clone();
+
+        // A root that disappears later is more likely unmounted than emptied, so its projects
+        // stay loaded instead of being removed.
+        let allow_missing_root = !self.loaded.swap(true, Ordering::Relaxed);
 
         async move {
-            let projects = task::spawn_blocking(move || {
-                let directory = match fs::read_dir(root.clone()) {
-                    Ok(dir) => dir,
-                    Err(error) => {
-                        println!("[FS - Skip] Failed to read directory: {}", error);
-                        return DashMap::new();
-                    }
-                };
-
-                let paths = directory
-                    .into_iter()
-                    .filter_map(|d| {
-                        let Ok(entry) = d else {
-                            return None;
-                        };
-
-                        let Ok(meta) = entry.metadata() else {
-                            return None;
-                        };
-
-                        meta.is_dir().then_some(entry.path().to_path_buf())
-                    })
-                    .collect::<Vec<PathBuf>>();
-
-                paths
-                    .into_iter()
-                    .filter_map(|directory| {
-                        let relative_path = match directory.strip_prefix(root.clone()) {
-                            Ok(ok) => ok,
-                            Err(err) => {
-                                tracing::error!(
-                                    "[FS - Skip] failed to strip prefix on {}: {}",
-                                    directory.display(),
-                                    err
-                                );
-                                return None;
-                            }
-                        };
-
-                        let project = match load_from_directory(&directory) {
-                            Ok(ok) => ok,
-                            Err(err) => {
-                                tracing::error!(
-                                    "[FS - Skip] failed to load project from directory {}: {}",
-                                    directory.display(),
-                                    err
-                                );
-                                return None;
-                            }
-                        };
-
-                        Some((
-                            relative_path.to_string_lossy().to_string(),
-                            Arc::new(project),
-                        ))
-                    })
-                    .collect::<DashMap<_, _>>()
+            let list_root = root.clone();
+            let project_datum = task::spawn_blocking(move || {
+                list_directory(list_root, allow_missing_root, &hashes)
             })
-            .await?;
+            .await??;
 
-            let diff = projects
+            // Projects are loaded under the hash they were listed with, rather than hashed again.
+            let listed_hashes = project_datum
                 .iter()
-                .map(|project| ProjectDiff::Created(project.key().to_string()))
-                .collect();
+                .filter_map(|d| Some((d.key.clone(), d.content_hash.clone()?)))
+                .collect::<HashMap<_, _>>();
+
+            let diff = data.calculate_diff(project_datum, &scope);
+            let to_refresh = Agent::get_refresh_list(&diff);
 
-            projects.into_iter().for_each(|(key, project)| {
-                let _ = data.projects.insert(key, project);
-            });
+            let refreshed_projects =
+                task::spawn_blocking(move || load_projects(root, to_refresh, listed_hashes))
+                    .await?;
+
+            let diff = Agent::get_diff_result(data, diff, refreshed_projects);
 
             Ok(diff)
         }
     }
 }
 
-fn load_from_directory(root: &PathBuf) -> anyhow::Result<Project> {
-    let files = WalkDir::new(root.clone())
+fn list_directory(
+    root: PathBuf,
+    allow_missing_root: bool,
+    hashes: &HashCache,
+) -> anyhow::Result<Vec<ProjectData>> {
+    if !root.exists() {
+        if !allow_missing_root {
+            bail!("Directory {} does not exist", root.display());
+        }
+
+        tracing::warn!(
+            "[FS - Skip all] Directory {} does not exist",
+            root.display()
+        );
+        return Ok(Vec::new());
+    }
+
+    let directory = fs::read_dir(root.clone())
+        .with_context(|| format!("failed to read directory {}", root.display()))?;
+
+    // A project that cannot be read fails the refresh, as leaving it out would unload it.
+    let mut project_datum = Vec::new();
+    let mut listed = HashSet::new();
+    for entry in directory {
+        let entry =
+            entry.with_context(|| format!("failed to read directory {}", root.display()))?;
+        if is_volume_metadata(&entry.file_name()) {
+            continue;
+        }
+
+        // Follows symlinks, which mounted volumes such as ConfigMaps use for their entries.
+        let meta = match fs::metadata(entry.path()) {
+            Ok(meta) => meta,
+            // Removed since the directory was read
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read {}", entry.path().display()));
+            }
+        };
+
+        if !meta.is_dir() {
+            continue;
+        }
+
+        let directory = entry.path();
+        let content_hash = hash_directory(&directory, hashes)
+            .with_context(|| format!("failed to read project directory {}", directory.display()))?;
+
+        project_datum.push(ProjectData {
+            key: entry.file_name().to_string_lossy().to_string(),
+            content_hash: Some(content_hash),
+        });
+        listed.insert(directory);
+    }
+
+    hashes.retain(&listed);
+
+    Ok(project_datum)
+}
+
+fn load_projects(
+    root: PathBuf,
+    keys: Vec<String>,
+    mut hashes: HashMap<String, Vec<u8>>,
+) -> DashMap<String, Arc<Project>> {
+    keys.into_iter()
+        .filter_map(|key| {
+            let directory = root.join(key.as_str());
+            let content_hash = hashes.remove(&key)?;
+
+            let mut project = match load_from_directory(&directory) {
+                Ok(ok) => ok,
+                Err(err) => {
+                    tracing::error!(
+                        "[FS - Skip] failed to load project from directory {}: {}",
+                        directory.display(),
+                        err
+                    );
+                    FailedProjectsRegistry::insert(content_hash);
+                    return None;
+                }
+            };
+
+            project.content_hash = Some(content_hash);
+            Some((key, Arc::new(project)))
+        })
+        .collect()
+}
+
+/// Kubernetes keeps the data of a mounted volume in `..data` and timestamped `..` directories,
+/// next to the symlinks it exposes.
+fn is_volume_metadata(name: &OsStr) -> bool {
+    name.as_bytes().starts_with(b"..")
+}
+
+/// Files of a project directory ordered by name, following symlinks.
+fn project_files(directory: &Path) -> anyhow::Result<Vec<DirEntry>> {
+    WalkDir::new(directory)
+        .follow_links(true)
+        .sort_by_file_name()
         .into_iter()
+        .filter_entry(|d| !is_volume_metadata(d.file_name()))
         .filter_ok(|d| d.file_type().is_file())
         .collect::<Result<Vec<_>, _>>()
-        .context("failed to load files")?;
+        .context("failed to load files")
+}
 
-    let project_json_path = Some(root.join(".config").join("project.json"));
-    let release_data = project_json_path
-        .map(|entry| {
-            let file_reader = File::open(entry).ok()?;
-            let content: ReleaseData = serde_json::from_reader(file_reader).ok()?;
+/// Hashes relative paths and contents of every file in the project directory. The files are only
+/// read when their size or modification time changed since the previous listing.
+fn hash_directory(directory: &Path, hashes: &HashCache) -> anyhow::Result<Vec<u8>> {
+    let files = project_files(directory)?;
+    let stamps = files
+        .iter()
+        .map(|entry| FileStamp::of(entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    hashes.get_or_hash(directory, stamps, || {
+        // Lengths are hashed ahead of paths and contents, so moving bytes between files changes
+        // the hash.
+        let mut hasher = Sha256::new();
+        for entry in &files {
+            let relative_path = entry.path().strip_prefix(directory)?;
+            let path = relative_path.as_os_str().as_bytes();
+            let content = fs::read(entry.path())?;
+
+            hasher.update((path.len() as u64).to_be_bytes());
+            hasher.update(path);
+            hasher.update((content.len() as u64).to_be_bytes());
+            hasher.update(&content);
+        }
+
+        Ok(hasher.finalize().to_vec())
+    })
+}
+
+fn load_from_directory(root: &PathBuf) -> anyhow::Result<Project> {
+    let files = project_files(root)?;
+
+    // Release data carries the access tokens, so a project whose data cannot be read must fail
+    // to load rather than be served without them.
+    let release_data = match File::open(root.join(".config").join("project.json")) {
+        Ok(file_reader) => {
+            let content: ReleaseData = serde_json::from_reader(file_reader)
+                .context("failed to parse .config/project.json")?;
 
             Some(content)
-        })
-        .flatten();
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => return Err(error).context("failed to open .config/project.json"),
+    };
 
     let projects = files
         .iter()
@@ -162,8 +270,8 @@ fn load_from_directory(root: &PathBuf) -> anyhow::Result<Project> {
         )
         .collect::<anyhow::Result<HashMap<String, FileDecisionGraph>>>();
 
-    Ok(Project {
-        engine: ImmutableLoader::new(projects?, release_data).into_engine(),
-        content_hash: None,
-    })
+    Ok(Project::new(
+        ImmutableLoader::new(projects?, release_data).into_engine(),
+        None,
+    ))
 }
@@ -8,6 +8,7 @@ use crate::config::{GcsProviderConfig, GlobalAgentConfig};
 use crate::immutable_loader::{ImmutableLoader, ProtectedZipArchive};
 use crate::provider::{
     AgentData, AgentDataProvider, FailedProjectsRegistry, Project, ProjectData, ProjectDiff,
+    RefreshScope,
 };
 use crate::util::prefix::Prefix;
 use anyhow::Context;
@@ -17,8 +18,9 @@ use dashmap::DashMap;
 use futures::StreamExt;
 use gcloud_storage::client::google_cloud_auth::credentials::CredentialsFile;
 use gcloud_storage::client::{Client, ClientConfig};
+use gcloud_storage::http::objects::Object;
 use gcloud_storage::http::objects::get::GetObjectRequest;
-use gcloud_storage::http::objects::list::ListObjectsRequest;
+use gcloud_storage::http::objects::list::{ListObjectsRequest, ListObjectsResponse};
 use zip::ZipArchive;
 
 #[derive(Clone)]
@@ -141,10 +143,7 @@ impl GcsProvider {
 
                     Some((
                         key,
-                        Arc::new(Project {
-                            engine,
-                            content_hash: Some(object.etag.into_bytes()),
-                        }),
+                        Arc::new(Project::new(engine, Some(object.etag.into_bytes()))),
                     ))
                 }
             })
@@ -161,41 +160,41 @@ impl AgentDataProvider for GcsProvider {
     fn load_data(
         &self,
         data: Arc<AgentData>,
+        scope: RefreshScope,
     ) -> impl Future<Output = anyhow::Result<Vec<ProjectDiff>>> + Send + 'static {
         let this = self.clone();
 
         async move {
-            let object_list = this
-                .client
-                .list_objects(&ListObjectsRequest {
+            let objects = list_all_objects(|page_token| {
+                let client = this.client.clone();
+                let request = ListObjectsRequest {
                     bucket: this.bucket.to_string(),
                     max_results: Some(1_000),
                     prefix: this.prefix.to_string(),
                     delimiter: Some(String::from("/")),
+                    page_token,
                     ..Default::default()
-                })
-                .await
-                .context("failed to list objects")?;
+                };
+
+                async move {
+                    client
+                        .list_objects(&request)
+                        .await
+                        .context("failed to list objects")
+                }
+            })
+            .await?;
 
-            let objects = object_list.items.unwrap_or_default();
             let project_datum = objects
                 .iter()
                 .map(|obj| ProjectData {
                     key: this.prefix.strip(obj.name.as_str().into()).into_owned(),
                     content_hash: Some(obj.etag.clone().into_bytes()),
                 })
-                .filter_map(|proj_data| {
-                    if FailedProjectsRegistry::has_failed(proj_data.content_hash.as_deref()) {
-                        return None;
-                    }
-                    if proj_data.key.is_empty() {
-                        return None;
-                    }
-                    Some(proj_data)
-                })
+                .filter(|proj_data| !proj_data.key.is_empty())
                 .collect::<Vec<_>>();
 
-            let diff = data.calculate_diff(project_datum);
+            let diff = data.calculate_diff(project_datum, &scope);
             let to_refresh = Agent::get_refresh_list(&diff);
 
             let refreshed_projects = this.generate_projects(to_refresh).await;
@@ -206,3 +205,86 @@ impl AgentDataProvider for GcsProvider {
         }
     }
 }
+
+/// Lists the objects of every page, following `next_page_token` until the last one.
+async fn list_all_objects<F, Fut>(mut list_page: F) -> anyhow::Result<Vec<Object>>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = anyhow::Result<ListObjectsResponse>>,
+{
+    let mut objects = Vec::new();
+    let mut page_token = None;
+    loop {
+        let page = list_page(page_token).await?;
+        objects.extend(page.items.unwrap_or_default());
+
+        page_token = page.next_page_token;
+        if page_token.is_none() {
+            return Ok(objects);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(names: &[&str], next_page_token: Option<&str>) -> ListObjectsResponse {
+        let items = names
+            .iter()
+            .map(|name| Object {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect();
+
+        ListObjectsResponse {
+            prefixes: None,
+            items: Some(items),
+            next_page_token: next_page_token.map(str::to_string),
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_every_page() {
+        let mut requested = Vec::new();
+        let objects = list_all_objects(|page_token| {
+            requested.push(page_token.clone());
+            let page = match page_token.as_deref() {
+                None => page(&["a.zip", "b.zip"], Some("page-2")),
+                Some("page-2") => ListObjectsResponse {
+                    items: None,
+                    ..page(&[], Some("page-3"))
+                },
+                Some("page-3") => page(&["c.zip"], None),
+                Some(token) => panic!("Unexpected page token {token}"),
+            };
+            async { Ok(page) }
+        })
+        .await
+        .unwrap();
+
+        let names = objects.iter().map(|o| o.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["a.zip", "b.zip", "c.zip"]);
+        assert_eq!(
+            requested,
+            [None, Some("page-2".to_string()), Some("page-3".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn list_error_fails_the_listing() {
+        let mut calls = 0;
+        let result = list_all_objects(|_| {
+            calls += 1;
+            let page = match calls {
+                1 => Ok(page(&["a.zip"], Some("page-2"))),
+                _ => Err(anyhow::anyhow!("failed to list objects")),
+            };
+            async { page }
+        })
+        .await;
+
+        assert!(result.is_err(), "A partial listing is not used");
+    }
+}
@@ -1,18 +1,30 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::future::Future;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::thread::available_parallelism;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{fs, io};
 
 use chrono::{DateTime, SecondsFormat, Utc};
 use dashmap::{DashMap, DashSet};
-use std::sync::OnceLock;
+use itertools::Itertools;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, OnceLock, RwLock};
 use strum_macros::AsRefStr;
+use tokio::sync::{Mutex, Semaphore};
 use tokio::time::Instant;
 use tokio::{task, time};
+use tokio_util::sync::CancellationToken;
 use zen_engine::DecisionEngine;
 
+use crate::audit::AuditLog;
+use crate::auth::Credentials;
 use crate::config::{EnvironmentConfig, GlobalAgentConfig, ProviderConfig};
 use crate::engine_ext::EngineExtension;
+use crate::prometheus;
 use crate::provider::azure_storage::AzureStorageProvider;
 use crate::provider::filesystem::FilesystemProvider;
 use crate::provider::gcs::GcsProvider;
@@ -35,23 +47,17 @@ enum AgentProvider {
 }
 
 impl AgentProvider {
-    async fn load_data(&self, data: Arc<AgentData>) -> anyhow::Result<Vec<ProjectDiff>> {
-        match self {
-            AgentProvider::Zip(zip) => zip.load_data(data).await,
-            AgentProvider::Filesystem(fs) => fs.load_data(data).await,
-            AgentProvider::S3(s3) => s3.load_data(data).await,
-            AgentProvider::AzureStorage(storage) => storage.load_data(data).await,
-            AgentProvider::GCS(gcs) => gcs.load_data(data).await,
-        }
-    }
-
-    fn should_refresh(&self) -> bool {
+    async fn load_data(
+        &self,
+        data: Arc<AgentData>,
+        scope: RefreshScope,
+    ) -> anyhow::Result<Vec<ProjectDiff>> {
         match self {
-            AgentProvider::Zip(_) => false,
-            AgentProvider::Filesystem(_) => false,
-            AgentProvider::S3(_) => true,
-            AgentProvider::AzureStorage(_) => true,
-            AgentProvider::GCS(_) => true,
+            AgentProvider::Zip(zip) => zip.load_data(data, scope).await,
+            AgentProvider::Filesystem(fs) => fs.load_data(data, scope).await,
+            AgentProvider::S3(s3) => s3.load_data(data, scope).await,
+            AgentProvider::AzureStorage(storage) => storage.load_data(data, scope).await,
+            AgentProvider::GCS(gcs) => gcs.load_data(data, scope).await,
         }
     }
 }
@@ -61,6 +67,12 @@ pub struct Agent {
     data: Arc<AgentData>,
     provider: Arc<AgentProvider>,
     config: Arc<EnvironmentConfig>,
+    refresh_status: Arc<RefreshStatus>,
+    shutdown: CancellationToken,
+    refresh_lock: Arc<Mutex<()>>,
+    audit: Arc<AuditLog>,
+    shadow_permits: Arc<Semaphore>,
+    credentials: Arc<RwLock<Arc<Credentials>>>,
 }
 
 impl Agent {
@@ -96,15 +108,26 @@ impl Agent {
         };
 
         tracing::info!("Created agent provider");
+        let audit = AuditLog::new(config.audit.as_ref()).await?;
+        let shadow_concurrency = config.shadow_concurrency.unwrap_or_else(|| {
+            let workers = available_parallelism().map_or(1, usize::from);
+            (workers / 2).max(1)
+        });
         let agent = Self {
-            data: Arc::new(Default::default()),
+            data: Arc::new(AgentData::new(config.release_history)),
             provider: Arc::new(provider),
             config: Arc::new(config),
+            refresh_status: Default::default(),
+            shutdown: CancellationToken::new(),
+            refresh_lock: Default::default(),
+            audit: Arc::new(audit),
+            shadow_permits: Arc::new(Semaphore::new(shadow_concurrency)),
+            credentials: Default::default(),
         };
 
         tracing::info!("Loading agent initial data");
         let start = Instant::now();
-        agent.refresh_data().await?;
+        agent.refresh_data(RefreshScope::All).await?;
 
         tracing::info!(duration = ?start.elapsed(), "Loaded agent initial data");
 
@@ -133,17 +156,15 @@ impl Agent {
                 ProjectDiff::Created(key) | ProjectDiff::Updated(key) => {
                     match refreshed_projects.get(key) {
                         Some(project) => {
-                            data.projects.insert(key.to_string(), project.clone());
+                            data.insert(key, project.clone());
                             Some(change)
                         }
-                        None => {
-                            data.projects.remove(key);
-                            None
-                        }
+                        // A release that failed to load leaves the previous one in place.
+                        None => None,
                     }
                 }
                 ProjectDiff::Removed(key) => {
-                    data.projects.remove(key);
+                    data.remove(key);
                     Some(change)
                 }
             })
@@ -151,18 +172,97 @@ impl Agent {
         diff
     }
 
+    /// Project served under a key or project id. Shadow and canary candidates are only
+    /// reachable through their project.
     pub fn project(&self, project: &str) -> Option<Arc<Project>> {
-        if let Some(p) = self.data.projects.get(project) {
-            return Some(p.clone());
-        };
+        let key = self.project_key(project)?;
+        self.data.projects.get(&key).map(|p| p.clone())
+    }
 
-        self.data.projects.iter().find_map(|p| {
-            let Some(rd) = p.engine.release_data() else {
-                return None;
-            };
+    /// Release currently served under the exact key, including candidate keys.
+    pub fn served_release(&self, key: &str) -> Option<Arc<Project>> {
+        self.data.projects.get(key).map(|p| p.clone())
+    }
 
-            (rd.project.id.deref() == project).then_some(p.to_owned())
-        })
+    /// Candidate release evaluated in the background alongside a project, loaded under the
+    /// `{project}.shadow` key.
+    pub fn shadow_project(&self, project: &str) -> Option<Arc<Project>> {
+        self.suffixed_project(project, SHADOW_SUFFIX)
+    }
+
+    /// Candidate release answering a share of a project's evaluations, loaded under the
+    /// `{project}.canary` key.
+    pub fn canary_project(&self, project: &str) -> Option<Arc<Project>> {
+        self.suffixed_project(project, CANARY_SUFFIX)
+    }
+
+    fn suffixed_project(&self, project: &str, suffix: &str) -> Option<Arc<Project>> {
+        let key = self.project_key(project)?;
+        self.data
+            .projects
+            .get(&format!("{key}{suffix}"))
+            .map(|p| p.clone())
+    }
+
+    fn project_key(&self, project: &str) -> Option<String> {
+        if is_candidate_key(project) {
+            return None;
+        }
+
+        if self.data.projects.contains_key(project) {
+            return Some(project.to_string());
+        }
+
+        // Candidates share the project id of the release they are compared against.
+        self.data
+            .projects
+            .iter()
+            .find(|p| {
+                !is_candidate_key(p.key())
+                    && p.engine
+                        .release_data()
+                        .is_some_and(|rd| rd.project.id.deref() == project)
+            })
+            .map(|p| p.key().clone())
+    }
+
+    pub fn config(&self) -> &EnvironmentConfig {
+        &self.config
+    }
+
+    pub fn refresh_status(&self) -> &RefreshStatus {
+        &self.refresh_status
+    }
+
+    /// Number of served projects, not counting shadow and canary candidates.
+    pub fn project_count(&self) -> usize {
+        self.data
+            .projects
+            .iter()
+            .filter(|p| !is_candidate_key(p.key()))
+            .count()
+    }
+
+    /// All served projects, ordered by key. Shadow and canary candidates are left out.
+    pub fn projects(&self) -> Vec<(String, Arc<Project>)> {
+        self.data
+            .projects
+            .iter()
+            .filter(|p| !is_candidate_key(p.key()))
+            .map(|p| (p.key().clone(), p.value().clone()))
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .collect()
+    }
+
+    /// Credentials of all served secured projects, see [Credentials].
+    pub fn credentials(&self) -> Arc<Credentials> {
+        self.credentials.read().unwrap().clone()
+    }
+
+    fn index_credentials(&self) {
+        let projects = self.projects();
+        let credentials = Credentials::new(projects.iter().map(|(_, p)| p));
+        *self.credentials.write().unwrap() = Arc::new(credentials);
     }
 
     #[tracing::instrument(
@@ -174,14 +274,30 @@ impl Agent {
             provider.password_protected = self.config.release_zip_password.is_some()
         )
     )]
-    async fn refresh_data(&self) -> anyhow::Result<Vec<ProjectDiff>> {
-        tracing::debug!("Refreshing agent data");
-        let diff = self.provider.load_data(self.data.clone()).await;
+    pub async fn refresh_data(&self, scope: RefreshScope) -> anyhow::Result<Vec<ProjectDiff>> {
+        // Polling and admin triggered reloads must not apply diffs concurrently.
+        let _guard = self.refresh_lock.lock().await;
+
+        tracing::debug!(?scope, "Refreshing agent data");
+        let start = Instant::now();
+        let diff = self.provider.load_data(self.data.clone(), scope).await;
+        prometheus::record_refresh(
+            &diff,
+            start.elapsed(),
+            self.project_count(),
+            FailedProjectsRegistry::len(),
+        );
+        self.refresh_status.record(diff.is_ok());
+
         if diff.as_ref().is_ok_and(|d| d.is_empty()) {
             tracing::debug!("No changes found during agent data refresh");
             return Ok(Default::default());
         }
 
+        if diff.is_ok() {
+            self.index_credentials();
+        }
+
         match &diff {
             Ok(data) => data.iter().for_each(|diff| match diff {
                 ProjectDiff::Created(project) => {
@@ -203,10 +319,6 @@ impl Agent {
     }
 
     pub fn register_refresh_data(&self) {
-        if !self.provider.should_refresh() {
-            return;
-        }
-
         let this = self.clone();
         task::spawn(async move {
             let duration = this.config.poll_interval.clone();
@@ -221,32 +333,252 @@ impl Agent {
 
             interval.tick().await;
 
-            loop {
-                interval.tick().await;
-                let _ = this.refresh_data().await;
+            // Refreshing only mutates agent data once loading completes, so it is safe to drop
+            // the loop at any await point.
+            let refresh_loop = async {
+                loop {
+                    interval.tick().await;
+                    let _ = this.refresh_data(RefreshScope::All).await;
+                }
+            };
+
+            tokio::select! {
+                _ = this.shutdown.cancelled() => {
+                    tracing::info!("Stopped agent data refresh job");
+                }
+                _ = refresh_loop => {}
             }
         });
     }
+
+    /// Releases retained in memory for a project, newest first.
+    pub fn releases(&self, key: &str) -> Option<ProjectReleases> {
+        let history = self.data.releases.get(key)?;
+
+        Some(ProjectReleases {
+            releases: history.releases.iter().cloned().collect(),
+            pinned: history.pinned.clone(),
+        })
+    }
+
+    /// Resolves a retained release of a project by slug or id.
+    pub fn project_release(&self, project: &str, release_id: &str) -> Option<Arc<Project>> {
+        let served = self.project(project)?;
+        if served.release_id().as_deref() == Some(release_id) {
+            return Some(served);
+        }
+
+        if let Some(canary) = self.canary_project(project)
+            && canary.release_id().as_deref() == Some(release_id)
+        {
+            return Some(canary);
+        }
+
+        // The served release is always part of its project's history.
+        self.data
+            .releases
+            .iter()
+            .find(|h| h.releases.iter().any(|p| Arc::ptr_eq(p, &served)))?
+            .find(release_id)
+    }
+
+    /// Serves a previously loaded release until it is unpinned. Returns false when the release
+    /// is not retained for the project.
+    pub async fn pin_release(&self, key: &str, release_id: &str) -> bool {
+        let _guard = self.refresh_lock.lock().await;
+
+        let Some(mut history) = self.data.releases.get_mut(key) else {
+            return false;
+        };
+
+        let Some(project) = history.find(release_id) else {
+            return false;
+        };
+
+        history.pinned = Some(Arc::from(release_id));
+        self.data.projects.insert(key.to_string(), project);
+        drop(history);
+        self.index_credentials();
+        tracing::info!("Project '{key}' pinned to release '{release_id}'.");
+
+        true
+    }
+
+    /// Restores the latest loaded release. Returns false when the project is not loaded.
+    pub async fn unpin_release(&self, key: &str) -> bool {
+        let _guard = self.refresh_lock.lock().await;
+
+        let Some(mut history) = self.data.releases.get_mut(key) else {
+            return false;
+        };
+
+        if history.pinned.take().is_some() {
+            if let Some(latest) = history.releases.front() {
+                self.data.projects.insert(key.to_string(), latest.clone());
+            }
+            drop(history);
+            self.index_credentials();
+            tracing::info!("Project '{key}' unpinned.");
+        }
+
+        true
+    }
+
+    /// Stops the refresh job and marks the agent as not ready.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    pub(crate) fn audit(&self) -> &Arc<AuditLog> {
+        &self.audit
+    }
+
+    /// Limits shadow evaluations, so a slow candidate cannot take over the workers.
+    pub(crate) fn shadow_permits(&self) -> &Arc<Semaphore> {
+        &self.shadow_permits
+    }
+
+    /// Writes out buffered audit records, once no more evaluations are served.
+    pub async fn close_audit(&self, timeout: Duration) {
+        self.audit.close(timeout).await;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RefreshStatus {
+    initial_load_completed: AtomicBool,
+    consecutive_failures: AtomicU32,
+}
+
+impl RefreshStatus {
+    fn record(&self, success: bool) {
+        if success {
+            self.initial_load_completed.store(true, Ordering::Relaxed);
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+        } else {
+            self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn initial_load_completed(&self) -> bool {
+        self.initial_load_completed.load(Ordering::Relaxed)
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures.load(Ordering::Relaxed)
+    }
 }
 
 type AgentDecisionEngine = DecisionEngine;
 
+const SHADOW_SUFFIX: &str = ".shadow";
+const CANARY_SUFFIX: &str = ".canary";
+
+fn is_candidate_key(key: &str) -> bool {
+    key.ends_with(SHADOW_SUFFIX) || key.ends_with(CANARY_SUFFIX)
+}
+
 #[derive(Debug)]
 pub struct Project {
     pub engine: AgentDecisionEngine,
     pub content_hash: Option<Vec<u8>>,
+    pub loaded_at: DateTime<Utc>,
+}
+
+impl Project {
+    pub fn new(engine: AgentDecisionEngine, content_hash: Option<Vec<u8>>) -> Self {
+        Self {
+            engine,
+            content_hash,
+            loaded_at: Utc::now(),
+        }
+    }
+
+    pub fn release_id(&self) -> Option<Arc<str>> {
+        self.engine.release_data().map(|rd| rd.release.id)
+    }
 }
 
 #[derive(Debug, Default)]
 pub struct AgentData {
     pub projects: Arc<DashMap<String, Arc<Project>>>,
+    releases: DashMap<String, ReleaseHistory>,
+    history_size: usize,
+}
+
+#[derive(Debug, Default)]
+struct ReleaseHistory {
+    /// Newest first, the latest loaded release is always retained
+    releases: VecDeque<Arc<Project>>,
+    pinned: Option<Arc<str>>,
+}
+
+impl ReleaseHistory {
+    fn find(&self, release_id: &str) -> Option<Arc<Project>> {
+        self.releases
+            .iter()
+            .find(|p| p.release_id().as_deref() == Some(release_id))
+            .cloned()
+    }
+}
+
+pub struct ProjectReleases {
+    pub releases: Vec<Arc<Project>>,
+    pub pinned: Option<Arc<str>>,
 }
 
 impl AgentData {
-    pub fn calculate_diff(&self, data: Vec<ProjectData>) -> Vec<ProjectDiff> {
+    pub fn new(history_size: usize) -> Self {
+        Self {
+            history_size: history_size.max(1),
+            ..Default::default()
+        }
+    }
+
+    /// Records a newly loaded release, serving it unless the project is pinned.
+    fn insert(&self, key: &str, project: Arc<Project>) {
+        let mut history = self.releases.entry(key.to_string()).or_default();
+        history.releases.push_front(project.clone());
+
+        let pinned = history.pinned.clone();
+        let mut retained = 0;
+        history.releases.retain(|p| {
+            retained += 1;
+            retained <= self.history_size || p.release_id() == pinned
+        });
+
+        if pinned.is_none() {
+            self.projects.insert(key.to_string(), project);
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        self.projects.remove(key);
+        self.releases.remove(key);
+    }
+
+    /// Latest loaded release, which differs from the served one while pinned.
+    fn latest(&self, key: &str) -> Option<Arc<Project>> {
+        match self.releases.get(key) {
+            Some(history) => history.releases.front().cloned(),
+            None => self.projects.get(key).map(|p| p.clone()),
+        }
+    }
+
+    pub fn calculate_diff(&self, data: Vec<ProjectData>, scope: &RefreshScope) -> Vec<ProjectDiff> {
+        let data = data
+            .into_iter()
+            .filter(|d| scope.includes(&d.key))
+            .collect::<Vec<_>>();
+
         let removal = self
             .projects
             .iter()
+            .filter(|e| scope.includes(e.key()))
             .filter_map(|e| {
                 let not_exists = data.iter().find(|o| &o.key == e.key()).is_none();
 
@@ -257,7 +589,12 @@ impl AgentData {
         let updates = data
             .into_iter()
             .filter_map(|obj| {
-                let Some(current_value) = self.projects.get(&obj.key) else {
+                // Failed releases are not retried until their content changes.
+                if FailedProjectsRegistry::has_failed(obj.content_hash.as_deref()) {
+                    return None;
+                }
+
+                let Some(current_value) = self.latest(&obj.key) else {
                     return Some(ProjectDiff::Created(obj.key));
                 };
 
@@ -277,10 +614,29 @@ pub trait AgentDataProvider {
     fn load_data(
         &self,
         data: Arc<AgentData>,
+        scope: RefreshScope,
     ) -> impl Future<Output = anyhow::Result<Vec<ProjectDiff>>> + Send + 'static;
 }
 
-#[derive(Debug)]
+/// Limits a refresh to a subset of the provider's projects.
+#[derive(Debug, Clone, Default)]
+pub enum RefreshScope {
+    #[default]
+    All,
+    Project(String),
+}
+
+impl RefreshScope {
+    pub fn includes(&self, key: &str) -> bool {
+        match self {
+            RefreshScope::All => true,
+            RefreshScope::Project(project) => project == key,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+#[serde(tag = "change", content = "project", rename_all = "camelCase")]
 pub enum ProjectDiff {
     Created(String),
     Removed(String),
@@ -306,11 +662,78 @@ impl FailedProjectsRegistry {
         Self::instance().insert(project_hash);
     }
 
+    pub fn len() -> usize {
+        Self::instance().len()
+    }
+
     pub fn has_failed(project_hash: Option<&[u8]>) -> bool {
         project_hash.map_or(false, |hash| Self::instance().contains(hash))
     }
 }
 
+/// SHA-256 digest used by providers that have no native etag (local files).
+pub fn content_hash(content: &[u8]) -> Vec<u8> {
+    Sha256::digest(content).to_vec()
+}
+
+/// Size and modification time of a local file, which tell whether its content may have changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStamp {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    /// Follows symlinks, so a swapped target changes the stamp.
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+}
+
+/// Content hashes of local releases, so a poll only reads and hashes releases whose files changed.
+#[derive(Debug, Default)]
+pub struct HashCache {
+    hashes: std::sync::Mutex<HashMap<PathBuf, (Vec<FileStamp>, Vec<u8>)>>,
+}
+
+impl HashCache {
+    /// Hash of the release at `path`, computed again only once `stamps` differ from the last time.
+    pub fn get_or_hash(
+        &self,
+        path: &Path,
+        stamps: Vec<FileStamp>,
+        hash: impl FnOnce() -> anyhow::Result<Vec<u8>>,
+    ) -> anyhow::Result<Vec<u8>> {
+        if let Some((cached, content_hash)) = self.hashes.lock().unwrap().get(path)
+            && *cached == stamps
+        {
+            return Ok(content_hash.clone());
+        }
+
+        let content_hash = hash()?;
+        let entry = (stamps, content_hash.clone());
+        self.hashes
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), entry);
+        Ok(content_hash)
+    }
+
+    /// Forgets releases that are no longer listed.
+    pub fn retain(&self, listed: &HashSet<PathBuf>) {
+        self.hashes
+            .lock()
+            .unwrap()
+            .retain(|path, _| listed.contains(path));
+    }
+}
+
 fn rounded_instant(target_duration: Duration) -> (SystemTime, Instant) {
     let now_system = SystemTime::now();
     let duration_since_epoch = now_system
@@ -7,6 +7,7 @@ use crate::config::{GlobalAgentConfig, S3ProviderConfig};
 use crate::immutable_loader::{ImmutableLoader, ProtectedZipArchive};
 use crate::provider::{
     AgentData, AgentDataProvider, FailedProjectsRegistry, Project, ProjectData, ProjectDiff,
+    RefreshScope,
 };
 use crate::util::prefix::Prefix;
 use aws_config::default_provider::credentials::DefaultCredentialsChain;
@@ -113,10 +114,7 @@ impl S3Provider {
 
                     Some((
                         key,
-                        Arc::new(Project {
-                            engine,
-                            content_hash: object.e_tag.map(|t| t.into_bytes()),
-                        }),
+                        Arc::new(Project::new(engine, object.e_tag.map(|t| t.into_bytes()))),
                     ))
                 }
             })
@@ -133,6 +131,7 @@ impl AgentDataProvider for S3Provider {
     fn load_data(
         &self,
         data: Arc<AgentData>,
+        scope: RefreshScope,
     ) -> impl Future<Output = anyhow::Result<Vec<ProjectDiff>>> + Send + 'static {
         let this = self.clone();
 
@@ -147,26 +146,25 @@ impl AgentDataProvider for S3Provider {
                 request_builder = request_builder.prefix(prefix)
             }
 
-            let response = request_builder.send().await?;
+            let mut pages = request_builder.into_paginator().send();
 
-            let objects = response.contents.unwrap_or_default();
-            let project_datum = objects
-                .into_iter()
-                .filter_map(|obj| {
+            let mut project_datum: Vec<ProjectData> = Vec::new();
+            while let Some(page) = pages.next().await {
+                let objects = page?.contents.unwrap_or_default();
+                let page_datum = objects.into_iter().filter_map(|obj| {
                     let key = this.prefix.strip(obj.key?.into()).into_owned();
                     if key.is_empty() {
                         return None;
                     }
 
                     let content_hash = obj.e_tag.map(|t| t.into_bytes());
-                    if FailedProjectsRegistry::has_failed(content_hash.as_deref()) {
-                        return None;
-                    }
                     Some(ProjectData { key, content_hash })
-                })
-                .collect();
+                });
+
+                project_datum.extend(page_datum);
+            }
 
-            let diff = data.calculate_diff(project_datum);
+            let diff = data.calculate_diff(project_datum, &scope);
 
             let to_refresh = Agent::get_refresh_list(&diff);
 
@@ -1,13 +1,18 @@
-use std::env;
-use std::fs::File;
 use std::future::Future;
+use std::io::Cursor;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{env, fs, io};
 
+use crate::Agent;
 use crate::config::{GlobalAgentConfig, ZipProviderConfig};
 use crate::immutable_loader::{ImmutableLoader, ProtectedZipArchive};
-use crate::provider::{AgentData, AgentDataProvider, Project, ProjectDiff};
-use anyhow::Context;
+use crate::provider::{
+    AgentData, AgentDataProvider, FailedProjectsRegistry, FileStamp, HashCache, Project,
+    ProjectData, ProjectDiff, RefreshScope, content_hash,
+};
+use anyhow::{Context, bail};
 use dashmap::DashMap;
 use itertools::Itertools;
 use tokio::task;
@@ -17,7 +22,10 @@ use zip::ZipArchive;
 #[derive(Debug)]
 pub struct ZipProvider {
     root_dir: PathBuf,
+    /// Set once the initial load ran, after which a missing root fails the refresh
+    loaded: AtomicBool,
     global_config: Arc<GlobalAgentConfig>,
+    hashes: Arc<HashCache>,
 }
 
 impl ZipProvider {
@@ -30,6 +38,8 @@ impl ZipProvider {
         Self {
             root_dir: root,
             global_config,
+            loaded: AtomicBool::new(false),
+            hashes: Default::default(),
         }
     }
 }
@@ -38,113 +48,156 @@ impl AgentDataProvider for ZipProvider {
     fn load_data(
         &self,
         data: Arc<AgentData>,
+        scope: RefreshScope,
     ) -> impl Future<Output = anyhow::Result<Vec<ProjectDiff>>> + Send + 'static {
         let root = self.root_dir.clone();
         let password = self.global_config.release_zip_password.clone();
+        let hashes = self.hashes.clone();
+
+        // A root that disappears later is more likely unmounted than emptied, so its projects
+        // stay loaded instead of being removed.
+        let allow_missing_root = !self.loaded.swap(true, Ordering::Relaxed);
 
         async move {
-            let projects = task::spawn_blocking(|| load_from_directory(root, password)).await?;
-            let diff = projects
-                .iter()
-                .map(|project| ProjectDiff::Created(project.key().to_string()))
-                .collect();
+            let list_root = root.clone();
+            let project_datum = task::spawn_blocking(move || {
+                list_directory(list_root, allow_missing_root, &hashes)
+            })
+            .await??;
+
+            let diff = data.calculate_diff(project_datum, &scope);
+            let to_refresh = Agent::get_refresh_list(&diff);
+
+            let refreshed_projects =
+                task::spawn_blocking(move || load_projects(root, to_refresh, password)).await?;
 
-            projects.into_iter().for_each(|(key, project)| {
-                let _ = data.projects.insert(key, project);
-            });
+            let diff = Agent::get_diff_result(data, diff, refreshed_projects);
 
             Ok(diff)
         }
     }
 }
 
-fn load_from_directory(root: PathBuf, password: Option<Arc<str>>) -> DashMap<String, Arc<Project>> {
-    let files = match WalkDir::new(root.clone())
+fn list_directory(
+    root: PathBuf,
+    allow_missing_root: bool,
+    hashes: &HashCache,
+) -> anyhow::Result<Vec<ProjectData>> {
+    if !root.exists() {
+        if !allow_missing_root {
+            bail!("Directory {} does not exist", root.display());
+        }
+
+        tracing::warn!(
+            "[Zip - Skip all] Directory {} does not exist",
+            root.display()
+        );
+        return Ok(Vec::new());
+    }
+
+    // Mounted volumes such as ConfigMaps expose their files as symlinks.
+    let files = WalkDir::new(root.clone())
         .max_depth(1)
+        .follow_links(true)
         .into_iter()
         .filter_ok(|d| d.file_type().is_file())
         .collect::<Result<Vec<_>, _>>()
-    {
-        Ok(dirs) => dirs,
-        Err(error) => {
-            tracing::error!(
-                "[Zip -Skip all] Failed to read directory {}: {}",
-                root.display(),
-                error
-            );
-            return DashMap::new();
-        }
-    };
+        .with_context(|| format!("failed to read directory {}", root.display()))?;
+
+    // A zip that cannot be read fails the refresh, as leaving it out would unload its project.
+    let mut project_datum = Vec::with_capacity(files.len());
+    for entry in &files {
+        let Some(key) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_suffix(".zip"))
+        else {
+            continue;
+        };
 
-    let projects = files
+        let stamp = match FileStamp::of(entry.path()) {
+            Ok(stamp) => stamp,
+            // Removed since the directory was read
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!("failed to read zip file {}", entry.path().display())
+                });
+            }
+        };
+
+        let content_hash = hashes.get_or_hash(entry.path(), vec![stamp], || {
+            let content = fs::read(entry.path())
+                .with_context(|| format!("failed to read zip file {}", entry.path().display()))?;
+            Ok(content_hash(&content))
+        })?;
+
+        project_datum.push(ProjectData {
+            key: key.to_string(),
+            content_hash: Some(content_hash),
+        });
+    }
+
+    let listed = files
         .iter()
-        .filter(|entry| {
-            entry
-                .file_name()
-                .to_str()
-                .is_some_and(|s| s.ends_with(".zip"))
-        })
-        .filter_map(|entry| {
-            let file_reader = match File::open(entry.path()).context("failed to open file") {
-                Ok(file_reader) => file_reader,
+        .map(|entry| entry.path().to_path_buf())
+        .collect();
+    hashes.retain(&listed);
+
+    Ok(project_datum)
+}
+
+fn load_projects(
+    root: PathBuf,
+    keys: Vec<String>,
+    password: Option<Arc<str>>,
+) -> DashMap<String, Arc<Project>> {
+    keys.into_iter()
+        .filter_map(|key| {
+            let path = root.join(format!("{key}.zip"));
+            let content = match fs::read(&path) {
+                Ok(content) => content,
                 Err(err) => {
                     tracing::error!(
                         "[Zip -Skip] failed to open zip file {}: {}",
-                        entry.path().display(),
-                        err
-                    );
-                    return None;
-                }
-            };
-            let path = match entry.path().strip_prefix(&root) {
-                Ok(stripped) => stripped
-                    .to_string_lossy()
-                    .trim_end_matches(".zip")
-                    .to_string(),
-                Err(err) => {
-                    tracing::error!(
-                        "[Zip -Skip] failed to strip prefix on {}: {}",
-                        entry.path().display(),
+                        path.display(),
                         err
                     );
                     return None;
                 }
             };
 
+            let content_hash = content_hash(&content);
             let archive = ProtectedZipArchive {
-                archive: match ZipArchive::new(file_reader) {
+                archive: match ZipArchive::new(Cursor::new(content)) {
                     Ok(archive) => archive,
                     Err(err) => {
                         tracing::error!(
                             "[Zip -Skip] failed unpack zip archive {}: {}",
-                            entry.path().display(),
+                            path.display(),
                             err
                         );
+                        FailedProjectsRegistry::insert(content_hash);
                         return None;
                     }
                 },
                 password: password.clone(),
             };
 
-            Some((
-                path,
-                Arc::new(Project {
-                    engine: match ImmutableLoader::try_from(archive) {
-                        Ok(loader) => loader.into_engine(),
-                        Err(err) => {
-                            tracing::error!(
-                                "[Zip -Skip] failed load into engine {}: {}",
-                                entry.path().display(),
-                                err
-                            );
-                            return None;
-                        }
-                    },
-                    content_hash: None,
-                }),
-            ))
-        })
-        .collect::<DashMap<_, _>>();
+            let engine = match ImmutableLoader::try_from(archive) {
+                Ok(loader) => loader.into_engine(),
+                Err(err) => {
+                    tracing::error!(
+                        "[Zip -Skip] failed load into engine {}: {}",
+                        path.display(),
+                        err
+                    );
+                    FailedProjectsRegistry::insert(content_hash);
+                    return None;
+                }
+            };
 
-    projects
+            Some((key, Arc::new(Project::new(engine, Some(content_hash)))))
+        })
+        .collect()
 }
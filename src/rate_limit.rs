@@ -0,0 +1,232 @@
+use crate::auth::{Auth, access_token};
+use crate::config::{RateLimitConfig, RateLimitKey};
+use crate::engine_ext::EngineExtension;
+use crate::prometheus;
+use crate::routes::engine::project_key;
+use crate::tls::ClientIdentity;
+use crate::{Agent, Project};
+use axum::extract::{ConnectInfo, RawPathParams, Request};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use dashmap::DashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Buckets are pruned once this many are tracked, dropping the ones that have refilled.
+const MAX_BUCKETS: usize = 10_000;
+/// Pruning walks every bucket, so it runs at most this often while over the limit.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Token buckets shared by all project routes.
+#[derive(Debug)]
+pub struct RateLimiter {
+    global: Option<RateLimitConfig>,
+    buckets: DashMap<String, Bucket>,
+    pruned_at: Mutex<Instant>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    full_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(global: Option<RateLimitConfig>) -> Self {
+        Self {
+            global,
+            buckets: DashMap::new(),
+            pruned_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Whether the bucket holds a request, without taking it. Returns how long until it does.
+    fn check(&self, key: &str, config: &RateLimitConfig) -> Result<(), Duration> {
+        if config.rate <= 0.0 {
+            return Ok(());
+        }
+
+        let Some(bucket) = self.buckets.get(key) else {
+            return Ok(());
+        };
+
+        let elapsed = Instant::now().duration_since(bucket.updated).as_secs_f64();
+        let tokens = (bucket.tokens + elapsed * config.rate).min(config.burst());
+        if tokens >= 1.0 {
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - tokens) / config.rate))
+        }
+    }
+
+    /// Takes one request from the bucket, or returns how long until one is available.
+    fn acquire(&self, key: String, config: &RateLimitConfig) -> Result<(), Duration> {
+        if config.rate <= 0.0 {
+            return Ok(());
+        }
+
+        let (rate, burst) = (config.rate, config.burst());
+        let now = Instant::now();
+        let result = {
+            let mut bucket = self.buckets.entry(key).or_insert(Bucket {
+                tokens: burst,
+                updated: now,
+                full_at: now,
+            });
+
+            let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+            bucket.updated = now;
+
+            let result = if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                Ok(())
+            } else {
+                Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+            };
+
+            bucket.full_at = now + Duration::from_secs_f64((burst - bucket.tokens) / rate);
+            result
+        };
+
+        if self.buckets.len() > MAX_BUCKETS {
+            self.prune(now);
+        }
+
+        result
+    }
+
+    /// Drops refilled buckets, unless another request pruned them within `PRUNE_INTERVAL`.
+    fn prune(&self, now: Instant) {
+        let Ok(mut pruned_at) = self.pruned_at.try_lock() else {
+            return;
+        };
+
+        if now.duration_since(*pruned_at) < PRUNE_INTERVAL {
+            return;
+        }
+
+        *pruned_at = now;
+        self.buckets.retain(|_, bucket| bucket.full_at > now);
+    }
+}
+
+/// Applies the project's limit, or else the global one, to routes with a `{project}` segment.
+pub async fn limit(
+    Extension(agent): Extension<Agent>,
+    Extension(auth): Extension<Auth>,
+    Extension(limiter): Extension<Arc<RateLimiter>>,
+    params: RawPathParams,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some((_, slug)) = params.iter().find(|(name, _)| *name == "project") else {
+        return next.run(request).await;
+    };
+
+    // Unknown slugs share a bucket and a label, so made up ones cannot add either.
+    let project_data = agent.project(slug);
+    let release_data = project_data.as_ref().and_then(|p| p.engine.release_data());
+    let project = project_data
+        .as_deref()
+        .map_or("unknown".to_string(), |p| project_key(slug, p));
+
+    // A project's own limit has separate buckets, while the global limit is shared by all projects.
+    let (scope, config) = match (release_data.and_then(|rd| rd.rate_limit), &limiter.global) {
+        (Some(config), _) => (project.as_str(), config),
+        (None, Some(config)) => ("*", config.clone()),
+        (None, None) => return next.run(request).await,
+    };
+
+    let subject = match config.key {
+        RateLimitKey::Token => {
+            // Tokens that do not verify are charged to their IP. Verifying may take an argon2
+            // hash, so an IP whose bucket is used up is turned away before its token is checked.
+            let ip = format!("ip:{}", client_ip(&request));
+            if let Err(retry_after) = limiter.check(&format!("{scope}/{ip}"), &config) {
+                prometheus::record_rate_limited(&project);
+                return too_many_requests(retry_after);
+            }
+
+            let client = request.extensions().get::<ClientIdentity>().cloned();
+            let headers = request.headers();
+            match verified_token(&auth, headers, client, project_data.as_deref()).await {
+                Some(token) => format!(
+                    "token:{}",
+                    access_token::hex(&access_token::fingerprint(token))
+                ),
+                None => ip,
+            }
+        }
+        RateLimitKey::Ip => format!("ip:{}", client_ip(&request)),
+        RateLimitKey::Project => format!("project:{project}"),
+    };
+
+    let bucket = format!("{scope}/{subject}");
+    match limiter.acquire(bucket, &config) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            prometheus::record_rate_limited(&project);
+            too_many_requests(retry_after)
+        }
+    }
+}
+
+/// Access token granted by the project, or else a bearer token that verified as a JWT. Tokens
+/// that do not verify fall back to the client IP, so made up ones cannot open fresh buckets.
+async fn verified_token<'a>(
+    auth: &Auth,
+    headers: &'a HeaderMap,
+    client: Option<ClientIdentity>,
+    project_data: Option<&Project>,
+) -> Option<&'a str> {
+    let access_token = headers
+        .get("X-Access-Token")
+        .and_then(|h| h.to_str().ok())
+        .filter(|h| !h.is_empty());
+
+    // Public projects accept any access token, so it tells nothing about the caller.
+    if let (Some(token), Some(project_data)) = (access_token, project_data)
+        && project_data.engine.release_data().is_some()
+        && project_data.engine.access_scope(token).await.is_some()
+    {
+        return Some(token);
+    }
+
+    let bearer = bearer_token(headers)?;
+    let caller = auth.caller(headers, client).ok()?;
+
+    caller.has_jwt().then_some(bearer)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Unix socket connections have no peer address and share a single bucket.
+fn client_ip(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, HeaderValue::from(seconds))],
+        Json(serde_json::json!({ "message": "Rate limit exceeded" })),
+    )
+        .into_response()
+}
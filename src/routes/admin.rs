@@ -0,0 +1,214 @@
+use crate::Agent;
+use crate::config::EnvironmentConfig;
+use crate::engine_ext::EngineExtension;
+use crate::provider::{ProjectDiff, RefreshScope};
+use axum::extract::Path;
+use axum::http::{HeaderMap, StatusCode};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/reload",
+    responses(
+        (status = OK, body = Vec<ProjectDiff>),
+        (status = UNAUTHORIZED, body = String),
+        (status = FORBIDDEN, body = String)
+    )
+)]
+pub async fn reload(
+    headers: HeaderMap,
+    Extension(agent): Extension<Agent>,
+) -> Result<Json<Vec<ProjectDiff>>, (StatusCode, String)> {
+    authorize(&agent, &headers)?;
+    refresh(&agent, RefreshScope::All).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/reload/{project}",
+    params(
+        ("project" = String, Path, description = "Project slug")
+    ),
+    responses(
+        (status = OK, body = Vec<ProjectDiff>),
+        (status = UNAUTHORIZED, body = String),
+        (status = FORBIDDEN, body = String)
+    )
+)]
+pub async fn reload_project(
+    headers: HeaderMap,
+    Extension(agent): Extension<Agent>,
+    Path(project): Path<String>,
+) -> Result<Json<Vec<ProjectDiff>>, (StatusCode, String)> {
+    authorize(&agent, &headers)?;
+    refresh(&agent, RefreshScope::Project(project)).await
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleasesResponse {
+    pinned_release_id: Option<Arc<str>>,
+    /// Retained releases, newest first
+    releases: Vec<ReleaseSummary>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseSummary {
+    release_id: Option<Arc<str>>,
+    release_version: Option<Arc<str>>,
+    loaded_at: DateTime<Utc>,
+    /// Whether this release is currently serving evaluations
+    active: bool,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PinReleaseRequest {
+    release_id: Arc<str>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/projects/{project}/releases",
+    params(
+        ("project" = String, Path, description = "Project slug")
+    ),
+    responses(
+        (status = OK, body = ReleasesResponse),
+        (status = NOT_FOUND, body = String)
+    )
+)]
+pub async fn releases(
+    headers: HeaderMap,
+    Extension(agent): Extension<Agent>,
+    Path(project): Path<String>,
+) -> Result<Json<ReleasesResponse>, (StatusCode, String)> {
+    authorize(&agent, &headers)?;
+    releases_response(&agent, &project)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/admin/projects/{project}/pin",
+    params(
+        ("project" = String, Path, description = "Project slug")
+    ),
+    request_body = PinReleaseRequest,
+    responses(
+        (status = OK, body = ReleasesResponse),
+        (status = NOT_FOUND, body = String)
+    )
+)]
+pub async fn pin_release(
+    headers: HeaderMap,
+    Extension(agent): Extension<Agent>,
+    Path(project): Path<String>,
+    Json(payload): Json<PinReleaseRequest>,
+) -> Result<Json<ReleasesResponse>, (StatusCode, String)> {
+    authorize(&agent, &headers)?;
+    if !agent.pin_release(&project, &payload.release_id).await {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "Release is not retained for this project".to_string(),
+        ));
+    }
+
+    releases_response(&agent, &project)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/projects/{project}/pin",
+    params(
+        ("project" = String, Path, description = "Project slug")
+    ),
+    responses(
+        (status = OK, body = ReleasesResponse),
+        (status = NOT_FOUND, body = String)
+    )
+)]
+pub async fn unpin_release(
+    headers: HeaderMap,
+    Extension(agent): Extension<Agent>,
+    Path(project): Path<String>,
+) -> Result<Json<ReleasesResponse>, (StatusCode, String)> {
+    authorize(&agent, &headers)?;
+    if !agent.unpin_release(&project).await {
+        return Err((StatusCode::NOT_FOUND, "Project not found".to_string()));
+    }
+
+    releases_response(&agent, &project)
+}
+
+fn releases_response(
+    agent: &Agent,
+    project: &str,
+) -> Result<Json<ReleasesResponse>, (StatusCode, String)> {
+    let (Some(history), Some(active)) = (agent.releases(project), agent.served_release(project))
+    else {
+        return Err((StatusCode::NOT_FOUND, "Project not found".to_string()));
+    };
+
+    let releases = history
+        .releases
+        .iter()
+        .map(|p| ReleaseSummary {
+            release_id: p.release_id(),
+            release_version: p.engine.release_data().map(|rd| rd.release.version),
+            loaded_at: p.loaded_at,
+            active: Arc::ptr_eq(p, &active),
+        })
+        .collect();
+
+    Ok(Json(ReleasesResponse {
+        pinned_release_id: history.pinned,
+        releases,
+    }))
+}
+
+fn authorize(agent: &Agent, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let config = agent.config();
+    if config.admin.tokens.is_empty() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Admin API is not enabled".to_string(),
+        ));
+    }
+
+    if !is_admin(config, headers) {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Whether the `X-Admin-Token` header holds one of the configured admin tokens.
+pub(crate) fn is_admin(config: &EnvironmentConfig, headers: &HeaderMap) -> bool {
+    let Some(admin_token) = headers.get("X-Admin-Token").and_then(|h| h.to_str().ok()) else {
+        return false;
+    };
+
+    config
+        .admin
+        .tokens
+        .iter()
+        .any(|token| bool::from(token.as_bytes().ct_eq(admin_token.as_bytes())))
+}
+
+async fn refresh(
+    agent: &Agent,
+    scope: RefreshScope,
+) -> Result<Json<Vec<ProjectDiff>>, (StatusCode, String)> {
+    match agent.refresh_data(scope).await {
+        Ok(diff) => Ok(Json(diff)),
+        Err(error) => Err((
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to reload projects: {error}"),
+        )),
+    }
+}
@@ -1,5 +1,7 @@
 use crate::Agent;
+use crate::auth::Auth;
 use crate::engine_ext::EngineExtension;
+use crate::tls::ClientIdentity;
 use axum::extract::Path;
 use axum::http::{HeaderMap, StatusCode};
 use axum::{Extension, Json};
@@ -12,34 +14,49 @@ use serde::Serialize;
         ("project" = String, Path, description = "Project slug or id")
     ),
     responses(
-        (status = OK, body = DecisionPointsResponse)
+        (status = OK, body = DecisionPointsResponse),
+        (status = UNAUTHORIZED, body = String),
+        (status = FORBIDDEN, body = String)
     )
 )]
 pub async fn decision_points(
     headers: HeaderMap,
     Extension(agent): Extension<Agent>,
+    Extension(auth): Extension<Auth>,
+    client: Option<Extension<ClientIdentity>>,
     Path(project): Path<String>,
 ) -> Result<Json<DecisionPointsResponse>, (StatusCode, String)> {
+    let caller = auth.caller(&headers, client.map(|Extension(c)| c))?;
     let Some(p) = agent.project(project.as_str()) else {
+        // Anonymous callers must not learn which projects exist.
+        auth.authenticate_metadata(&caller, &agent).await?;
         return Err((StatusCode::NOT_FOUND, "Project not found".to_string()));
     };
 
-    let access_token = headers
-        .get("X-Access-Token")
-        .map(|h| h.to_str().unwrap_or(""))
-        .unwrap_or_default();
-
-    if !p.engine.can_access(access_token) {
+    let Some(scope) = caller.scope(&project, &p).await else {
         return Err((
             StatusCode::UNAUTHORIZED,
             "Invalid X-Access-Token Header".to_string(),
         ));
+    };
+
+    // Access to a secured project already authenticates the caller.
+    if p.engine.release_data().is_none() {
+        auth.authenticate_metadata(&caller, &agent).await?;
+    }
+
+    if !scope.entrypoints {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Listing entrypoints is not allowed".to_string(),
+        ));
     }
 
     let entrypoints = p
         .engine
         .decision_keys()
         .into_iter()
+        .filter(|path| scope.allows_decision(path))
         .map(|path| Entrypoint {
             path,
             r#type: "graph".to_string(),
@@ -1,22 +1,43 @@
-use crate::Agent;
+use crate::audit::{AuditLog, AuditRecord};
+use crate::auth::{Auth, Caller};
+use crate::canary::Canary;
+use crate::config::{EnvironmentConfig, TracePolicy};
+use crate::data::release_data::TokenScope;
 use crate::engine_ext::EngineExtension;
+use crate::prometheus::{self, EvaluationLabels};
+use crate::routes::admin;
+use crate::shadow::ShadowEvaluation;
+use crate::tls::ClientIdentity;
+use crate::util::redact::redact;
+use crate::{Agent, Project};
 use anyhow::{Context, anyhow};
+use axum::body::Body;
 use axum::extract::Path;
-use axum::http::{HeaderMap, StatusCode};
+use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::{IntoResponse, Response};
 use axum::{Extension, Json};
+use chrono::Utc;
+use futures::{StreamExt, TryStreamExt, future, stream};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::io;
 use std::sync::Arc;
-use tokio_util::task::LocalPoolHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio_util::codec::{FramedRead, LinesCodec};
+use tokio_util::io::StreamReader;
+use tokio_util::task::{AbortOnDropHandle, LocalPoolHandle};
 use tracing::Span;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
-use zen_engine::EvaluationOptions;
+use zen_engine::{EvaluationOptions, EvaluationTraceKind};
 
 #[derive(Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct EvaluateRequest {
     context: Value,
     trace: Option<bool>,
+    /// Fails with 409 unless this release can be served, same as `X-Release-Id`
+    release_id: Option<Arc<str>>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -33,6 +54,9 @@ pub struct EvaluateResponse {
 pub struct EvaluateDetailsResponse {
     release_id: Option<Arc<str>>,
     version_id: Option<Arc<str>>,
+    /// Set when the canary release answered
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    canary: bool,
 }
 
 #[utoipa::path(
@@ -40,17 +64,23 @@ pub struct EvaluateDetailsResponse {
     path = "/api/projects/{project}/evaluate/{*key}",
     params(
         ("project" = String, Path, description = "Project slug or id"),
-        ("key" = String, Path, description = "Key (path) of decision model")
+        ("key" = String, Path, description = "Key (path) of decision model"),
+        ("X-Release-Id" = Option<String>, Header, description = "Release that must answer the evaluation"),
+        ("X-Evaluation-Timeout" = Option<u64>, Header, description = "Milliseconds the evaluation may run, capped by the configured timeout. Only the response is cut short: expressions and decision tables run to completion, and function nodes are stopped by the engine after the configured timeout, not this one.")
     ),
     request_body = EvaluateRequest,
     responses(
-        (status = OK, body = EvaluateResponse)
+        (status = OK, body = EvaluateResponse),
+        (status = CONFLICT, description = "Requested release is not available"),
+        (status = GATEWAY_TIMEOUT, description = "Evaluation did not finish in time. It may keep running on its worker after the response")
     )
 )]
 pub async fn evaluate(
     headers: HeaderMap,
     Extension(local_pool): Extension<LocalPoolHandle>,
     Extension(agent): Extension<Agent>,
+    Extension(auth): Extension<Auth>,
+    client: Option<Extension<ClientIdentity>>,
     Path((project, key)): Path<(Arc<str>, Arc<str>)>,
     Json(payload): Json<EvaluateRequest>,
 ) -> Result<Json<EvaluateResponse>, EvaluateError> {
@@ -71,94 +101,838 @@ pub async fn evaluate(
         span.set_attribute("project.key", release_data.project.key.clone());
     };
 
-    let access_token = headers
-        .get("X-Access-Token")
-        .map(|h| h.to_str().unwrap_or("").to_string())
-        .unwrap_or_default();
+    let caller = auth.caller(&headers, client.map(|Extension(c)| c))?;
+    let scope = authorize(&caller, &project, &project_data).await?;
+
+    let release_id = requested_release_id(&headers, payload.release_id)?;
+    let (project_data, scope) = select_release(
+        &agent,
+        &caller,
+        &project,
+        project_data,
+        scope,
+        release_id.as_deref(),
+    )
+    .await?;
+
+    let trace = payload.trace.unwrap_or(false);
+    let canary = match canary_split(&agent, &project, &headers, release_id.as_deref()) {
+        Some(c) => route_canary(&c, &caller, &project, &key, &payload.context, trace).await,
+        None => None,
+    };
+    let is_canary = canary.is_some();
+    let (project_data, scope) = canary.unwrap_or((project_data, scope));
+    let identity = caller.identity(&project, &project_data).await;
+
+    if !scope.allows_decision(&key) {
+        return Err(forbidden_decision());
+    }
+
+    if trace {
+        authorize_trace(&scope, &headers, agent.config())?;
+    }
+
+    let pool = EvaluationPool::new(local_pool, &agent, &headers, identity)?;
+    let options = EvaluationOptions {
+        trace,
+        max_depth: max_depth(&project_data, agent.config().max_depth),
+    };
+    // The shadow candidate mirrors the primary release, so it is not compared with the canary.
+    let shadow = agent.shadow_project(&project).filter(|_| !is_canary);
+    let result = evaluate_pinned(
+        &pool,
+        &project,
+        project_data.clone(),
+        shadow,
+        key.clone(),
+        payload.context,
+        options,
+    )
+    .await?;
+
+    let release_data = project_data.engine.release_data();
 
-    if !project_data.engine.can_access(access_token.as_str()) {
+    let release_id = release_data.map(|r| r.release.id.clone());
+    let version_id = project_data.engine.get_version(&key);
+
+    Ok(Json(EvaluateResponse {
+        graph_response: result,
+        details: EvaluateDetailsResponse {
+            version_id,
+            release_id,
+            canary: is_canary,
+        },
+    }))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluateBatchRequest {
+    items: Vec<EvaluateBatchItem>,
+    trace: Option<bool>,
+    /// Fails with 409 unless this release can be served, same as `X-Release-Id`
+    release_id: Option<Arc<str>>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct EvaluateBatchItem {
+    /// Overrides the decision key from the path for this item
+    key: Option<Arc<str>>,
+    context: Value,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluateBatchResponse {
+    details: EvaluateBatchDetailsResponse,
+    results: Vec<EvaluateBatchItemResponse>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluateBatchDetailsResponse {
+    release_id: Option<Arc<str>>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase", untagged)]
+pub enum EvaluateBatchItemResponse {
+    #[serde(rename_all = "camelCase")]
+    Success {
+        key: Arc<str>,
+        release_id: Option<Arc<str>>,
+        version_id: Option<Arc<str>>,
+
+        #[serde(flatten)]
+        graph_response: Value,
+    },
+    Error {
+        key: Arc<str>,
+        error: Value,
+    },
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/projects/{project}/evaluate-batch/{*key}",
+    params(
+        ("project" = String, Path, description = "Project slug or id"),
+        ("key" = String, Path, description = "Default key (path) of decision model"),
+        ("X-Release-Id" = Option<String>, Header, description = "Release that must answer the evaluations"),
+        ("X-Evaluation-Timeout" = Option<u64>, Header, description = "Milliseconds each evaluation may run, capped by the configured timeout. Only the response is cut short: expressions and decision tables run to completion, and function nodes are stopped by the engine after the configured timeout, not this one.")
+    ),
+    request_body = EvaluateBatchRequest,
+    responses(
+        (status = OK, body = EvaluateBatchResponse),
+        (status = CONFLICT, description = "Requested release is not available"),
+        (status = PAYLOAD_TOO_LARGE, description = "Batch has more items than allowed")
+    )
+)]
+pub async fn evaluate_batch(
+    headers: HeaderMap,
+    Extension(local_pool): Extension<LocalPoolHandle>,
+    Extension(agent): Extension<Agent>,
+    Extension(auth): Extension<Auth>,
+    client: Option<Extension<ClientIdentity>>,
+    Path((project, key)): Path<(Arc<str>, Arc<str>)>,
+    Json(payload): Json<EvaluateBatchRequest>,
+) -> Result<Json<EvaluateBatchResponse>, EvaluateError> {
+    let span = Span::current();
+
+    span.set_attribute("params.project", project.clone());
+    span.set_attribute("params.key", key.clone());
+    span.set_attribute("batch.size", payload.items.len() as i64);
+
+    if payload.items.len() > MAX_BATCH_ITEMS {
         let error = (
-            StatusCode::UNAUTHORIZED,
-            anyhow!("Invalid X-Access-Token Header"),
+            StatusCode::PAYLOAD_TOO_LARGE,
+            anyhow!("Batch has more than {MAX_BATCH_ITEMS} items, use evaluate-stream instead"),
+        );
+        return Err(error.into());
+    }
+
+    let Some(project_data) = agent.project(&project) else {
+        let error = (StatusCode::NOT_FOUND, anyhow!("Project not found"));
+        return Err(error.into());
+    };
+
+    let caller = auth.caller(&headers, client.map(|Extension(c)| c))?;
+    let scope = authorize(&caller, &project, &project_data).await?;
+
+    let release_id = requested_release_id(&headers, payload.release_id)?;
+    let canary = canary_split(&agent, &project, &headers, release_id.as_deref());
+    let (project_data, scope) = select_release(
+        &agent,
+        &caller,
+        &project,
+        project_data,
+        scope,
+        release_id.as_deref(),
+    )
+    .await?;
+    let identity = caller.identity(&project, &project_data).await;
+
+    let trace = payload.trace.unwrap_or(false);
+    if trace {
+        authorize_trace(&scope, &headers, agent.config())?;
+    }
+
+    let pool = EvaluationPool::new(local_pool, &agent, &headers, identity)?;
+    let shadow = agent.shadow_project(&project);
+    let evaluations = payload.items.into_iter().map(|item| {
+        let item_key = item.key.unwrap_or_else(|| key.clone());
+        let (agent, project, caller, canary) = (&agent, &project, &caller, &canary);
+        let (pool, project_data, scope) = (&pool, &project_data, &scope);
+        let shadow = shadow.clone();
+
+        async move {
+            let routed = match canary {
+                Some(c) => route_canary(c, caller, project, &item_key, &item.context, trace).await,
+                None => None,
+            };
+            let (item_data, item_scope, item_pool, shadow) = match routed {
+                Some((release, scope)) => {
+                    let identity = caller.identity(project, &release).await;
+                    (release, scope, pool.with_caller(identity), None)
+                }
+                None => (project_data.clone(), scope.clone(), pool.clone(), shadow),
+            };
+
+            if !item_scope.allows_decision(&item_key) {
+                return EvaluateBatchItemResponse::Error {
+                    key: item_key,
+                    error: forbidden_decision().to_value(),
+                };
+            }
+
+            let options = EvaluationOptions {
+                trace,
+                max_depth: max_depth(&item_data, agent.config().max_depth),
+            };
+            evaluate_item(
+                &item_pool,
+                project,
+                item_data,
+                shadow,
+                item_key,
+                item.context,
+                options,
+            )
+            .await
+        }
+    });
+
+    // Same as evaluate_stream, at most one evaluation per pinned worker is queued at a time.
+    let concurrency = pool.local_pool.num_threads();
+    let results = stream::iter(evaluations)
+        .buffered(concurrency)
+        .collect::<Vec<_>>()
+        .await;
+    let release_id = project_data
+        .engine
+        .release_data()
+        .map(|r| r.release.id.clone());
+
+    Ok(Json(EvaluateBatchResponse {
+        details: EvaluateBatchDetailsResponse { release_id },
+        results,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/projects/{project}/evaluate-stream/{*key}",
+    params(
+        ("project" = String, Path, description = "Project slug or id"),
+        ("key" = String, Path, description = "Default key (path) of decision model"),
+        ("X-Release-Id" = Option<String>, Header, description = "Release that must answer the evaluations"),
+        ("X-Evaluation-Timeout" = Option<u64>, Header, description = "Milliseconds each evaluation may run, capped by the configured timeout. Only the response is cut short: expressions and decision tables run to completion, and function nodes are stopped by the engine after the configured timeout, not this one.")
+    ),
+    request_body(
+        content = EvaluateBatchItem,
+        content_type = "application/x-ndjson",
+        description = "One evaluation item per line"
+    ),
+    responses(
+        (status = OK, body = EvaluateBatchItemResponse, content_type = "application/x-ndjson"),
+        (status = CONFLICT, description = "Requested release is not available")
+    )
+)]
+pub async fn evaluate_stream(
+    headers: HeaderMap,
+    Extension(local_pool): Extension<LocalPoolHandle>,
+    Extension(agent): Extension<Agent>,
+    Extension(auth): Extension<Auth>,
+    client: Option<Extension<ClientIdentity>>,
+    Path((project, key)): Path<(Arc<str>, Arc<str>)>,
+    body: Body,
+) -> Result<Response, EvaluateError> {
+    let span = Span::current();
+
+    span.set_attribute("params.project", project.clone());
+    span.set_attribute("params.key", key.clone());
+
+    let is_ndjson = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|ct| ct.starts_with(APPLICATION_NDJSON));
+    if !is_ndjson {
+        let error = (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            anyhow!("Expected request with `Content-Type: {APPLICATION_NDJSON}`"),
         );
         return Err(error.into());
     }
 
-    let cloned_project_data = project_data.clone();
-    let cloned_key = key.clone();
-    let result = local_pool
-        .spawn_pinned(move || async move {
-            cloned_project_data
-                .engine
-                .evaluate_with_opts(
-                    &cloned_key,
-                    payload.context.into(),
-                    EvaluationOptions {
-                        trace: payload.trace.unwrap_or(false),
-                        max_depth: 10,
-                    },
+    let Some(project_data) = agent.project(&project) else {
+        let error = (StatusCode::NOT_FOUND, anyhow!("Project not found"));
+        return Err(error.into());
+    };
+
+    let caller = auth.caller(&headers, client.map(|Extension(c)| c))?;
+    let scope = authorize(&caller, &project, &project_data).await?;
+
+    let release_id = requested_release_id(&headers, None)?;
+    let canary = canary_split(&agent, &project, &headers, release_id.as_deref()).map(Arc::new);
+    let (project_data, scope) = select_release(
+        &agent,
+        &caller,
+        &project,
+        project_data,
+        scope,
+        release_id.as_deref(),
+    )
+    .await?;
+    let identity = caller.identity(&project, &project_data).await;
+
+    let caller = Arc::new(caller);
+    let scope = Arc::new(scope);
+    let pool = EvaluationPool::new(local_pool, &agent, &headers, identity)?;
+
+    let default_depth = agent.config().max_depth;
+    let shadow = agent.shadow_project(&project);
+    let reader = StreamReader::new(body.into_data_stream().map_err(io::Error::other));
+    let lines = FramedRead::new(reader, LinesCodec::new_with_max_length(MAX_LINE_LENGTH));
+
+    // Evaluations are polled in order, at most one per pinned worker, so a slow consumer
+    // stops the body from being read any further.
+    let concurrency = pool.local_pool.num_threads();
+    let results = lines
+        .filter(|line| {
+            let is_blank = line.as_ref().is_ok_and(|l| l.trim().is_empty());
+            future::ready(!is_blank)
+        })
+        .map(move |line| {
+            let pool = pool.clone();
+            let project = project.clone();
+            let project_data = project_data.clone();
+            let shadow = shadow.clone();
+            let canary = canary.clone();
+            let caller = caller.clone();
+            let scope = scope.clone();
+            let key = key.clone();
+
+            async move {
+                let item = line.context("Failed to read line").and_then(|l| {
+                    serde_json::from_str::<EvaluateBatchItem>(&l).context("Invalid line")
+                });
+
+                let item = match item {
+                    Ok(item) => item,
+                    Err(error) => {
+                        return EvaluateBatchItemResponse::Error {
+                            key,
+                            error: EvaluateError::from(error).to_value(),
+                        };
+                    }
+                };
+
+                let item_key = item.key.unwrap_or(key);
+                let routed = match canary.as_deref() {
+                    Some(c) => {
+                        route_canary(c, &caller, &project, &item_key, &item.context, false).await
+                    }
+                    None => None,
+                };
+                let (item_data, allowed, pool, shadow) = match routed {
+                    Some((release, scope)) => {
+                        let identity = caller.identity(&project, &release).await;
+                        let allowed = scope.allows_decision(&item_key);
+                        (release, allowed, pool.with_caller(identity), None)
+                    }
+                    None => (project_data, scope.allows_decision(&item_key), pool, shadow),
+                };
+
+                if !allowed {
+                    return EvaluateBatchItemResponse::Error {
+                        key: item_key,
+                        error: forbidden_decision().to_value(),
+                    };
+                }
+
+                let options = EvaluationOptions {
+                    trace: false,
+                    max_depth: max_depth(&item_data, default_depth),
+                };
+                evaluate_item(
+                    &pool,
+                    &project,
+                    item_data,
+                    shadow,
+                    item_key,
+                    item.context,
+                    options,
                 )
                 .await
-                .map(|s| serde_json::to_value(s).context("Failed to serialize value"))
-                .map_err(|e| anyhow::Error::msg(e.to_string()))
+            }
         })
-        .await
-        .expect("Thread failed to join");
-    let result = match result {
-        Ok(result) => result,
-        Err(error) => {
-            tracing::error!(error = debug(&error), "Failed to evaluate decision model");
+        .buffered(concurrency)
+        .map(|item| {
+            let mut line = serde_json::to_vec(&item)?;
+            line.push(b'\n');
+
+            Ok::<_, serde_json::Error>(line)
+        });
+
+    Ok((
+        [(header::CONTENT_TYPE, APPLICATION_NDJSON)],
+        Body::from_stream(results),
+    )
+        .into_response())
+}
+
+const APPLICATION_NDJSON: &str = "application/x-ndjson";
+const MAX_LINE_LENGTH: usize = 16 * 1024 * 1024;
+const MAX_BATCH_ITEMS: usize = 1_000;
+
+/// Release requested through `X-Release-Id` or the request body, which must agree when both are set.
+fn requested_release_id(
+    headers: &HeaderMap,
+    body_release_id: Option<Arc<str>>,
+) -> Result<Option<Arc<str>>, EvaluateError> {
+    let header_release_id = match headers.get("X-Release-Id") {
+        Some(h) => Some(Arc::<str>::from(
+            h.to_str().context("Invalid X-Release-Id Header")?,
+        )),
+        None => None,
+    };
+
+    match (header_release_id, body_release_id) {
+        (Some(header), Some(body)) if header != body => {
+            Err(anyhow!("X-Release-Id Header '{header}' does not match releaseId '{body}'").into())
+        }
+        (header, body) => Ok(header.or(body)),
+    }
+}
+
+/// Timeout requested through `X-Evaluation-Timeout`, which can only lower the configured one.
+fn evaluation_timeout(
+    headers: &HeaderMap,
+    config: &EnvironmentConfig,
+) -> Result<Duration, EvaluateError> {
+    let Some(header) = headers.get("X-Evaluation-Timeout") else {
+        return Ok(config.evaluation_timeout);
+    };
+
+    let millis = header
+        .to_str()
+        .ok()
+        .and_then(|h| h.trim().parse::<u64>().ok())
+        .context("Invalid X-Evaluation-Timeout Header, expected milliseconds")?;
+
+    Ok(Duration::from_millis(millis).min(config.evaluation_timeout))
+}
+
+fn forbidden_decision() -> EvaluateError {
+    let error = (StatusCode::FORBIDDEN, anyhow!("Decision is not allowed"));
+    error.into()
+}
+
+/// Traces need the caller's scope to allow them, and the agent's [TracePolicy] on top.
+fn authorize_trace(
+    scope: &TokenScope,
+    headers: &HeaderMap,
+    config: &EnvironmentConfig,
+) -> Result<(), EvaluateError> {
+    let allowed = scope.trace
+        && match config.trace_policy {
+            TracePolicy::Allow => true,
+            TracePolicy::Deny => false,
+            TracePolicy::Admin => admin::is_admin(config, headers),
+        };
+
+    if !allowed {
+        return Err(forbidden_trace());
+    }
+
+    Ok(())
+}
+
+/// Depth limit of the release answering the evaluation, which can only lower the agent's.
+fn max_depth(project_data: &Project, limit: u8) -> u8 {
+    project_data
+        .engine
+        .release_data()
+        .and_then(|rd| rd.max_depth)
+        .map_or(limit, |depth| depth.min(limit))
+}
+
+fn forbidden_trace() -> EvaluateError {
+    let error = (StatusCode::FORBIDDEN, anyhow!("Trace is not allowed"));
+    error.into()
+}
+
+/// Traffic split towards the project's canary, unless the caller asked for a specific release.
+fn canary_split(
+    agent: &Agent,
+    project: &str,
+    headers: &HeaderMap,
+    release_id: Option<&str>,
+) -> Option<Canary> {
+    if release_id.is_some() {
+        return None;
+    }
+
+    Canary::new(agent.canary_project(project)?, headers)
+}
+
+/// Canary release and the caller's scope on it when the evaluation falls into its share of
+/// traffic. Evaluations the canary does not authorize stay on the primary release.
+async fn route_canary(
+    canary: &Canary,
+    caller: &Caller,
+    project: &str,
+    key: &str,
+    context: &Value,
+    trace: bool,
+) -> Option<(Arc<Project>, TokenScope)> {
+    let release = canary.route(context)?;
+    let scope = caller.scope(project, &release).await?;
+    if !scope.allows_decision(key) || (trace && !scope.trace) {
+        return None;
+    }
+
+    Some((release, scope))
+}
+
+/// Scope the caller holds on a release, failing with 401 when it has no access.
+async fn authorize(
+    caller: &Caller,
+    project: &str,
+    project_data: &Project,
+) -> Result<TokenScope, EvaluateError> {
+    caller.scope(project, project_data).await.ok_or_else(|| {
+        let error = (
+            StatusCode::UNAUTHORIZED,
+            anyhow!("Invalid X-Access-Token Header"),
+        );
+        error.into()
+    })
+}
+
+/// Picks the retained release matching the requested id, defaulting to the served one. Access
+/// is checked again against a different release, as tokens may have been revoked since.
+async fn select_release(
+    agent: &Agent,
+    caller: &Caller,
+    project: &str,
+    project_data: Arc<Project>,
+    scope: TokenScope,
+    release_id: Option<&str>,
+) -> Result<(Arc<Project>, TokenScope), EvaluateError> {
+    let Some(release_id) = release_id else {
+        return Ok((project_data, scope));
+    };
+
+    match agent.project_release(project, release_id) {
+        Some(release) if Arc::ptr_eq(&release, &project_data) => Ok((project_data, scope)),
+        Some(release) => {
+            let scope = authorize(caller, project, &release).await?;
+            Ok((release, scope))
+        }
+        None => {
+            let loaded = project_data.release_id().unwrap_or_default();
+            let error = (
+                StatusCode::CONFLICT,
+                anyhow!("Release '{release_id}' is not available, loaded release is '{loaded}'"),
+            );
+            Err(error.into())
+        }
+    }
+}
+
+/// Key of the project in its release data, so requests by slug and by id are recorded alike.
+pub(crate) fn project_key(project: &str, project_data: &Project) -> String {
+    project_data
+        .engine
+        .release_data()
+        .map_or_else(|| project.to_string(), |rd| rd.project.key.to_string())
+}
+
+/// Worker pool evaluations are pinned to, how long and how deep each of them may run, the audit
+/// log recording them and the permits shadow evaluations run under.
+#[derive(Clone)]
+struct EvaluationPool {
+    local_pool: LocalPoolHandle,
+    timeout: Duration,
+    max_depth: u8,
+    audit: Arc<AuditLog>,
+    caller: Option<Arc<str>>,
+    shadow_permits: Arc<Semaphore>,
+}
+
+impl EvaluationPool {
+    fn new(
+        local_pool: LocalPoolHandle,
+        agent: &Agent,
+        headers: &HeaderMap,
+        caller: Option<Arc<str>>,
+    ) -> Result<Self, EvaluateError> {
+        Ok(Self {
+            local_pool,
+            timeout: evaluation_timeout(headers, agent.config())?,
+            max_depth: agent.config().max_depth,
+            audit: agent.audit().clone(),
+            caller,
+            shadow_permits: agent.shadow_permits().clone(),
+        })
+    }
+
+    /// Same pool recording evaluations under another caller identity.
+    fn with_caller(&self, caller: Option<Arc<str>>) -> Self {
+        Self {
+            caller,
+            ..self.clone()
+        }
+    }
+}
+
+async fn evaluate_item(
+    pool: &EvaluationPool,
+    project: &str,
+    project_data: Arc<Project>,
+    shadow: Option<Arc<Project>>,
+    key: Arc<str>,
+    context: Value,
+    options: EvaluationOptions,
+) -> EvaluateBatchItemResponse {
+    let result = evaluate_pinned(
+        pool,
+        project,
+        project_data.clone(),
+        shadow,
+        key.clone(),
+        context,
+        options,
+    )
+    .await;
+
+    match result {
+        Ok(graph_response) => EvaluateBatchItemResponse::Success {
+            release_id: project_data.release_id(),
+            version_id: project_data.engine.get_version(&key),
+            key,
+            graph_response,
+        },
+        Err(error) => EvaluateBatchItemResponse::Error {
+            key,
+            error: error.to_value(),
+        },
+    }
+}
+
+/// Evaluates on a pinned worker and records the outcome in the audit log.
+async fn evaluate_pinned(
+    pool: &EvaluationPool,
+    project: &str,
+    project_data: Arc<Project>,
+    shadow: Option<Arc<Project>>,
+    key: Arc<str>,
+    context: Value,
+    options: EvaluationOptions,
+) -> Result<Value, EvaluateError> {
+    let record = pool.audit.is_enabled().then(|| AuditRecord {
+        timestamp: Utc::now(),
+        project: project_key(project, &project_data),
+        decision: key.clone(),
+        release_id: project_data.release_id(),
+        version_id: project_data.engine.get_version(&key),
+        caller: pool.caller.clone(),
+        context: context.clone(),
+        output: None,
+        error: None,
+        latency_ms: 0.0,
+    });
+
+    let start = Instant::now();
+    let result =
+        evaluate_on_worker(pool, project, project_data, shadow, key, context, options).await;
+
+    if let Some(mut record) = record {
+        record.latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+        match &result {
+            Ok(response) => record.output = response.get("result").cloned(),
+            Err(error) => record.error = Some(error.to_value()),
+        }
+        pool.audit.record(record);
+    }
+
+    result
+}
+
+async fn evaluate_on_worker(
+    pool: &EvaluationPool,
+    project: &str,
+    project_data: Arc<Project>,
+    shadow: Option<Arc<Project>>,
+    key: Arc<str>,
+    context: Value,
+    options: EvaluationOptions,
+) -> Result<Value, EvaluateError> {
+    // Unknown keys come from the caller, so they share a label to keep cardinality bounded.
+    let labels = EvaluationLabels {
+        project: project_key(project, &project_data),
+        decision: if project_data.engine.has_decision(&key) {
+            key.to_string()
+        } else {
+            "unknown".to_string()
+        },
+        release_id: project_data
+            .engine
+            .release_data()
+            .map(|r| r.release.id.to_string())
+            .unwrap_or_default(),
+    };
+
+    let shadow = shadow.map(|candidate| ShadowEvaluation {
+        project: labels.project.clone(),
+        decision: labels.decision.clone(),
+        key: key.clone(),
+        max_depth: max_depth(&candidate, pool.max_depth),
+        candidate,
+        context: context.clone(),
+        timeout: pool.timeout,
+    });
+
+    let trace_redaction = project_data
+        .engine
+        .release_data()
+        .filter(|_| options.trace)
+        .map(|rd| rd.trace_redaction);
+
+    let start = Instant::now();
+    let task = AbortOnDropHandle::new(pool.local_pool.spawn_pinned(move || async move {
+        project_data
+            .engine
+            .evaluate_with_opts(&key, context.into(), options)
+            .await
+            .map(|s| serde_json::to_value(s).context("Failed to serialize value"))
+            .map_err(|e| engine_error(&e))
+    }));
+
+    // Dropping the handle on timeout only answers early. The engine evaluates expressions and
+    // decision tables without yielding, so the pinned worker stays busy until they finish, and
+    // function nodes run until the process wide limit set in main.rs, whatever the timeout here.
+    let result = match tokio::time::timeout(pool.timeout, task).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(error)) => {
+            prometheus::record_evaluation(&labels, start.elapsed(), false);
+            tracing::error!(error = debug(&error), "Evaluation task failed");
+            let error = (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                anyhow!("Evaluation task failed"),
+            );
             return Err(error.into());
         }
+        Err(_) => {
+            prometheus::record_evaluation(&labels, start.elapsed(), false);
+            tracing::warn!(timeout = ?pool.timeout, "Evaluation timed out");
+            return Err(EvaluateError::Timeout(pool.timeout));
+        }
     };
+    prometheus::record_evaluation(&labels, start.elapsed(), matches!(result, Ok(Ok(_))));
 
     let result = match result {
         Ok(result) => result,
-        Err(error) => {
-            tracing::error!(error = debug(&error), "Failed to serialize the response.");
-            return Err(error.into());
+        Err(mut error) => {
+            tracing::error!(error = %error["message"], "Failed to evaluate decision model");
+            if let (Some(trace), Some(patterns)) = (error.get_mut("trace"), &trace_redaction) {
+                redact(trace, patterns);
+            }
+
+            return Err(EvaluateError::EngineError(error));
         }
     };
 
-    let release_data = project_data.engine.release_data();
+    match result {
+        Ok(mut result) => {
+            if let (Some(trace), Some(patterns)) = (result.get_mut("trace"), &trace_redaction) {
+                redact(trace, patterns);
+            }
 
-    let release_id = release_data.map(|r| r.release.id.clone());
-    let version_id = project_data.engine.get_version(&key);
+            if let Some(shadow) = shadow {
+                shadow.spawn(&pool.local_pool, &pool.shadow_permits, result.clone());
+            }
 
-    Ok(Json(EvaluateResponse {
-        graph_response: result,
-        details: EvaluateDetailsResponse {
-            version_id,
-            release_id,
-        },
-    }))
+            Ok(result)
+        }
+        Err(error) => {
+            tracing::error!(error = debug(&error), "Failed to serialize the response.");
+            Err(error.into())
+        }
+    }
+}
+
+/// Serializes an engine error on the worker, as the error itself cannot leave it. The engine only
+/// records a trace when one was requested, and it is redacted like the trace of a successful
+/// evaluation.
+fn engine_error(error: &zen_engine::EvaluationError) -> Value {
+    let mut value = error
+        .serialize_with_mode(serde_json::value::Serializer, EvaluationTraceKind::Default)
+        .unwrap_or_default();
+    if let Value::Object(map) = &mut value {
+        map.insert("message".to_string(), Value::String(error.to_string()));
+    }
+
+    value
 }
 
 pub enum EvaluateError {
-    EngineError(Box<zen_engine::EvaluationError>),
+    EngineError(Value),
     Anyhow((StatusCode, anyhow::Error)),
+    Timeout(Duration),
 }
 
-impl IntoResponse for EvaluateError {
-    fn into_response(self) -> Response {
+impl EvaluateError {
+    fn status(&self) -> StatusCode {
         match self {
-            EvaluateError::EngineError(error) => (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::to_value(&error).unwrap_or_default()),
-            )
-                .into_response(),
-            EvaluateError::Anyhow((status, error)) => (
-                status,
-                Json(serde_json::json!({ "message": error.to_string() })),
-            )
-                .into_response(),
+            EvaluateError::EngineError(_) => StatusCode::BAD_REQUEST,
+            EvaluateError::Anyhow((status, _)) => *status,
+            EvaluateError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            EvaluateError::EngineError(error) => error.clone(),
+            EvaluateError::Anyhow((_, error)) => {
+                serde_json::json!({ "message": error.to_string() })
+            }
+            EvaluateError::Timeout(timeout) => {
+                let millis = timeout.as_millis();
+                serde_json::json!({
+                    "type": "Timeout",
+                    "source": format!("Evaluation timed out after {millis}ms"),
+                    "timeoutMs": millis,
+                })
+            }
         }
     }
 }
 
-impl From<Box<zen_engine::EvaluationError>> for EvaluateError {
-    fn from(value: Box<zen_engine::EvaluationError>) -> Self {
-        Self::EngineError(value)
+impl IntoResponse for EvaluateError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self.to_value())).into_response()
     }
 }
 
@@ -1,4 +1,9 @@
-use axum::http::StatusCode;
+use crate::Agent;
+use crate::prometheus;
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde::Serialize;
 
 #[utoipa::path(
     get,
@@ -11,6 +16,94 @@ pub async fn health() -> (StatusCode, &'static str) {
     (StatusCode::OK, "healthy")
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/ready",
+    responses(
+        (status = OK, body = ReadinessResponse),
+        (status = SERVICE_UNAVAILABLE, body = ReadinessResponse)
+    )
+)]
+pub async fn ready(Extension(agent): Extension<Agent>) -> (StatusCode, Json<ReadinessResponse>) {
+    let config = &agent.config().readiness;
+    let refresh_status = agent.refresh_status();
+    let projects = agent.project_count();
+    let consecutive_refresh_failures = refresh_status.consecutive_failures();
+
+    let mut reasons = Vec::new();
+    if agent.is_shutting_down() {
+        reasons.push("Agent is shutting down".to_string());
+    }
+
+    if !refresh_status.initial_load_completed() {
+        reasons.push("Initial project load has not completed".to_string());
+    }
+
+    if projects < config.min_projects {
+        reasons.push(format!(
+            "{projects} projects loaded, at least {} required",
+            config.min_projects
+        ));
+    }
+
+    config
+        .required_projects
+        .iter()
+        .filter(|slug| agent.project(slug).is_none())
+        .for_each(|slug| reasons.push(format!("Required project '{slug}' is not loaded")));
+
+    let not_ready = !reasons.is_empty();
+    let degraded = config
+        .max_refresh_failures
+        .is_some_and(|max| consecutive_refresh_failures >= max);
+    if degraded {
+        reasons.push(format!(
+            "{consecutive_refresh_failures} consecutive refreshes failed"
+        ));
+    }
+
+    let status = if not_ready {
+        ReadinessStatus::NotReady
+    } else if degraded {
+        ReadinessStatus::Degraded
+    } else {
+        ReadinessStatus::Ready
+    };
+
+    // Loaded projects keep being served while refreshes fail, so degraded agents stay in rotation.
+    let status_code = match status {
+        ReadinessStatus::Ready | ReadinessStatus::Degraded => StatusCode::OK,
+        ReadinessStatus::NotReady => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (
+        status_code,
+        Json(ReadinessResponse {
+            status,
+            reasons,
+            projects,
+            consecutive_refresh_failures,
+        }),
+    )
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessResponse {
+    pub status: ReadinessStatus,
+    pub reasons: Vec<String>,
+    pub projects: usize,
+    pub consecutive_refresh_failures: u32,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ReadinessStatus {
+    Ready,
+    NotReady,
+    Degraded,
+}
+
 #[utoipa::path(
     get,
     path = "/api/version",
@@ -24,3 +117,17 @@ pub async fn version() -> (StatusCode, String) {
 
     (StatusCode::OK, service_version)
 }
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (status = OK, body = String, content_type = "text/plain; version=0.0.4")
+    )
+)]
+pub async fn metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        prometheus::render(),
+    )
+}
@@ -1,3 +1,4 @@
+pub mod admin;
 pub mod decision_points;
 pub mod engine;
 pub mod infra;
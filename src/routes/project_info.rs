@@ -1,11 +1,62 @@
 use crate::Agent;
+use crate::auth::{Auth, AuthError};
 use crate::engine_ext::EngineExtension;
+use crate::tls::ClientIdentity;
 use axum::extract::Path;
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 use std::sync::Arc;
 
+#[utoipa::path(
+    get,
+    path = "/api/projects",
+    responses(
+        (status = OK, body = ProjectsResponse),
+        (status = UNAUTHORIZED, body = String)
+    )
+)]
+pub async fn projects(
+    headers: HeaderMap,
+    Extension(agent): Extension<Agent>,
+    Extension(auth): Extension<Auth>,
+    client: Option<Extension<ClientIdentity>>,
+) -> Result<Json<ProjectsResponse>, (StatusCode, String)> {
+    let caller = auth.caller(&headers, client.map(|Extension(c)| c))?;
+
+    let authenticated = caller.is_authenticated(&agent).await;
+    if auth.requires_metadata_auth() && !authenticated {
+        return Err(AuthError::authentication_required().into());
+    }
+
+    let mut projects = Vec::new();
+    for (key, p) in agent.projects() {
+        let release_data = p.engine.release_data();
+        // Callers holding no credential of a secured project are only shown public ones, so a
+        // guessed token is not verified against every project.
+        if !authenticated && release_data.is_some() {
+            continue;
+        }
+
+        if caller.scope(&key, &p).await.is_none() {
+            continue;
+        }
+
+        projects.push(ProjectSummary {
+            key,
+            project_id: release_data.as_ref().map(|rd| rd.project.id.clone()),
+            project_key: release_data.as_ref().map(|rd| rd.project.key.clone()),
+            release_id: release_data.as_ref().map(|rd| rd.release.id.clone()),
+            release_version: release_data.as_ref().map(|rd| rd.release.version.clone()),
+            decision_count: p.engine.decision_keys().len(),
+            loaded_at: p.loaded_at,
+        });
+    }
+
+    Ok(Json(ProjectsResponse { projects }))
+}
+
 #[utoipa::path(
     get,
     path = "/api/projects/{project}",
@@ -13,17 +64,36 @@ use std::sync::Arc;
         ("project" = String, Path, description = "Project slug or id")
     ),
     responses(
-        (status = OK, body = ProjectInfo)
+        (status = OK, body = ProjectInfo),
+        (status = UNAUTHORIZED, body = String)
     )
 )]
 pub async fn project_info(
+    headers: HeaderMap,
     Extension(agent): Extension<Agent>,
+    Extension(auth): Extension<Auth>,
+    client: Option<Extension<ClientIdentity>>,
     Path(project): Path<String>,
 ) -> Result<Json<ProjectInfo>, (StatusCode, String)> {
+    let caller = auth.caller(&headers, client.map(|Extension(c)| c))?;
     let Some(p) = agent.project(project.as_str()) else {
+        // Anonymous callers must not learn which projects exist.
+        auth.authenticate_metadata(&caller, &agent).await?;
         return Err((StatusCode::NOT_FOUND, "Project not found".to_string()));
     };
 
+    if caller.scope(&project, &p).await.is_none() {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Invalid X-Access-Token Header".to_string(),
+        ));
+    }
+
+    // Access to a secured project already authenticates the caller.
+    if p.engine.release_data().is_none() {
+        auth.authenticate_metadata(&caller, &agent).await?;
+    }
+
     let Some(release_data) = p.engine.release_data() else {
         return Err((
             StatusCode::BAD_REQUEST,
@@ -46,3 +116,19 @@ pub struct ProjectInfo {
     pub release_id: Arc<str>,
     pub release_version: Arc<str>,
 }
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProjectsResponse {
+    pub projects: Vec<ProjectSummary>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProjectSummary {
+    pub key: String,
+    pub project_id: Option<Arc<str>>,
+    pub project_key: Option<Arc<str>>,
+    pub release_id: Option<Arc<str>>,
+    pub release_version: Option<Arc<str>>,
+    pub decision_count: usize,
+    pub loaded_at: DateTime<Utc>,
+}
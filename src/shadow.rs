@@ -0,0 +1,135 @@
+use crate::Project;
+use crate::prometheus;
+use anyhow::{Context, anyhow};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio_util::task::{AbortOnDropHandle, LocalPoolHandle};
+use zen_engine::EvaluationOptions;
+
+/// Differing paths reported per mismatch, so a broken candidate cannot flood the logs.
+const MAX_REPORTED_PATHS: usize = 20;
+
+pub struct ShadowEvaluation {
+    pub project: String,
+    pub decision: String,
+    pub key: Arc<str>,
+    pub candidate: Arc<Project>,
+    pub context: Value,
+    pub max_depth: u8,
+    /// Same limit as the primary evaluation, so a stuck candidate gives its permit back
+    pub timeout: Duration,
+}
+
+impl ShadowEvaluation {
+    /// Evaluates the candidate in the background and compares its result with the primary one.
+    /// Skipped when all permits are taken, so shadowing never queues up behind live traffic.
+    pub fn spawn(self, local_pool: &LocalPoolHandle, permits: &Arc<Semaphore>, primary: Value) {
+        let Ok(permit) = permits.clone().try_acquire_owned() else {
+            prometheus::record_shadow_evaluation(&self.project, &self.decision, "skipped");
+            return;
+        };
+
+        let local_pool = local_pool.clone();
+
+        tokio::spawn(async move {
+            let _permit = permit;
+            let key = self.key.clone();
+            let candidate = self.candidate.clone();
+            let context = self.context.clone();
+            let options = EvaluationOptions {
+                trace: false,
+                max_depth: self.max_depth,
+            };
+
+            let task = AbortOnDropHandle::new(local_pool.spawn_pinned(move || async move {
+                candidate
+                    .engine
+                    .evaluate_with_opts(&key, context.into(), options)
+                    .await
+                    .map_err(|e| anyhow::Error::msg(e.to_string()))
+                    .and_then(|r| serde_json::to_value(r).context("Failed to serialize value"))
+            }));
+
+            // As for the primary, the timeout only stops waiting. The candidate keeps its worker
+            // until it finishes or the engine interrupts a function node.
+            match tokio::time::timeout(self.timeout, task).await {
+                Ok(result) => self.compare(primary, result),
+                Err(_) => self.record_error(anyhow!("Timed out after {:?}", self.timeout)),
+            }
+        });
+    }
+
+    fn compare(
+        &self,
+        primary: Value,
+        candidate: Result<anyhow::Result<Value>, tokio::task::JoinError>,
+    ) {
+        let candidate = match candidate {
+            Ok(Ok(candidate)) => candidate,
+            Ok(Err(error)) => return self.record_error(error),
+            Err(error) => return self.record_error(error.into()),
+        };
+
+        let mut paths = Vec::new();
+        diff_paths(&primary["result"], &candidate["result"], "", &mut paths);
+        if paths.is_empty() {
+            prometheus::record_shadow_evaluation(&self.project, &self.decision, "match");
+            return;
+        }
+
+        prometheus::record_shadow_evaluation(&self.project, &self.decision, "mismatch");
+        tracing::warn!(
+            project = self.project,
+            decision = self.decision,
+            paths = ?paths,
+            "Shadow evaluation result differs from the primary release"
+        );
+    }
+
+    fn record_error(&self, error: anyhow::Error) {
+        prometheus::record_shadow_evaluation(&self.project, &self.decision, "error");
+        tracing::warn!(
+            project = self.project,
+            decision = self.decision,
+            error = ?error,
+            "Shadow evaluation failed"
+        );
+    }
+}
+
+/// Collects JSON pointers where the two values differ.
+fn diff_paths(primary: &Value, candidate: &Value, path: &str, paths: &mut Vec<String>) {
+    if paths.len() >= MAX_REPORTED_PATHS || primary == candidate {
+        return;
+    }
+
+    match (primary, candidate) {
+        (Value::Object(a), Value::Object(b)) => {
+            a.keys()
+                .chain(b.keys().filter(|k| !a.contains_key(*k)))
+                .for_each(|k| {
+                    let child = format!("{path}/{}", k.replace('~', "~0").replace('/', "~1"));
+                    let (a, b) = (a.get(k), b.get(k));
+                    diff_paths(
+                        a.unwrap_or(&Value::Null),
+                        b.unwrap_or(&Value::Null),
+                        &child,
+                        paths,
+                    );
+                });
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            a.iter()
+                .zip(b)
+                .enumerate()
+                .for_each(|(i, (a, b))| diff_paths(a, b, &format!("{path}/{i}"), paths));
+        }
+        _ => paths.push(if path.is_empty() {
+            "/".to_string()
+        } else {
+            path.to_string()
+        }),
+    }
+}
@@ -0,0 +1,131 @@
+use crate::config::HttpSslConfig;
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use futures::future::BoxFuture;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tower_http::add_extension::AddExtension;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// Names of a verified client certificate, the subject common names followed by its SAN entries.
+/// Empty when the peer did not present a certificate.
+#[derive(Debug, Clone, Default)]
+pub struct ClientIdentity {
+    names: Arc<[String]>,
+}
+
+impl ClientIdentity {
+    fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, cert) = X509Certificate::from_der(der).ok()?;
+
+        let common_names = cert
+            .subject()
+            .iter_common_name()
+            .filter_map(|cn| cn.as_str().ok())
+            .map(str::to_string);
+
+        let alt_names = cert
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .into_iter()
+            .flat_map(|san| san.value.general_names.iter())
+            .filter_map(|name| match name {
+                GeneralName::DNSName(name) => Some(name.to_string()),
+                GeneralName::URI(uri) => Some(uri.to_string()),
+                GeneralName::RFC822Name(email) => Some(email.to_string()),
+                _ => None,
+            });
+
+        Some(Self {
+            names: common_names.chain(alt_names).collect(),
+        })
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+/// Terminates TLS and attaches the peer's [ClientIdentity] to every request of the connection.
+#[derive(Clone)]
+pub struct ClientCertAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl ClientCertAcceptor {
+    pub fn new(config: RustlsConfig) -> Self {
+        Self {
+            inner: RustlsAcceptor::new(config),
+        }
+    }
+}
+
+impl<I, S> Accept<I, S> for ClientCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = <RustlsAcceptor as Accept<I, S>>::Stream;
+    type Service = AddExtension<S, ClientIdentity>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let handshake = self.inner.accept(stream, service);
+
+        Box::pin(async move {
+            let (stream, service) = handshake.await?;
+            let identity = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| ClientIdentity::from_der(cert))
+                .unwrap_or_default();
+
+            Ok((stream, AddExtension::new(service, identity)))
+        })
+    }
+}
+
+/// Re-reads certificate files every `interval` and swaps them into `rustls_config` when they
+/// change. A rotation that fails to load is logged and the previous certificate keeps serving.
+pub fn reload_certificates(ssl: HttpSslConfig, rustls_config: RustlsConfig, interval: Duration) {
+    if !ssl.has_files() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut current = ssl.load_pem().await.ok();
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            let pem = match ssl.load_pem().await {
+                Ok(pem) => pem,
+                Err(error) => {
+                    tracing::warn!("Failed to read SSL certificates: {error:?}");
+                    continue;
+                }
+            };
+
+            if current.as_ref() == Some(&pem) {
+                continue;
+            }
+
+            match ssl.server_config(&pem) {
+                Ok(config) => {
+                    rustls_config.reload_from_config(Arc::new(config));
+                    current = Some(pem);
+                    tracing::info!("Reloaded SSL certificates");
+                }
+                Err(error) => tracing::warn!("Failed to reload SSL certificates: {error:?}"),
+            }
+        }
+    });
+}
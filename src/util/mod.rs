@@ -1 +1,3 @@
+pub mod glob;
 pub mod prefix;
+pub mod redact;
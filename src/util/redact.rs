@@ -0,0 +1,40 @@
+use crate::util::glob::glob_match;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Replaces redacted values, keeping their key so the shape of the document is preserved.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Replaces every value whose dot separated path matches one of the glob `patterns`. Array items
+/// are addressed by their index, e.g. `*.input.applicants.0.ssn`.
+pub fn redact(value: &mut Value, patterns: &[Arc<str>]) {
+    if !patterns.is_empty() {
+        redact_at(value, "", patterns);
+    }
+}
+
+fn redact_at(value: &mut Value, path: &str, patterns: &[Arc<str>]) {
+    let children: Box<dyn Iterator<Item = (String, &mut Value)>> = match value {
+        Value::Object(map) => Box::new(map.iter_mut().map(|(k, v)| (k.clone(), v))),
+        Value::Array(items) => Box::new(
+            items
+                .iter_mut()
+                .enumerate()
+                .map(|(i, v)| (i.to_string(), v)),
+        ),
+        _ => return,
+    };
+
+    for (segment, child) in children {
+        let child_path = match path {
+            "" => segment,
+            _ => format!("{path}.{segment}"),
+        };
+
+        if patterns.iter().any(|p| glob_match(p, &child_path)) {
+            *child = Value::String(REDACTED.to_string());
+        } else {
+            redact_at(child, &child_path, patterns);
+        }
+    }
+}
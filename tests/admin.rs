@@ -0,0 +1,202 @@
+mod support;
+
+use agent::config::{AdminConfig, EnvironmentConfig};
+use axum::Router;
+use axum::http::{Method, StatusCode};
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+use support::{evaluate, request, send, temp_dir, zip_app};
+
+async fn admin_app(root_dir: &Path, tokens: &[&str]) -> Router {
+    let config = EnvironmentConfig {
+        admin: AdminConfig {
+            tokens: tokens.iter().map(|t| t.to_string()).collect(),
+        },
+        release_history: 5,
+        ..Default::default()
+    };
+
+    zip_app(root_dir.to_str().unwrap(), config).await.1
+}
+
+async fn admin(app: Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    send(
+        app,
+        request(method, uri, body, &[("X-Admin-Token", "admin-token")]),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn admin_reload() {
+    let root_dir = temp_dir("admin-reload");
+    fs::copy("tests/data/sample-project.zip", root_dir.join("first.zip")).unwrap();
+
+    let app = admin_app(&root_dir, &["admin-token"]).await;
+
+    fs::copy("tests/data/sample-project.zip", root_dir.join("second.zip")).unwrap();
+    fs::copy("tests/data/sample-project.zip", root_dir.join("third.zip")).unwrap();
+    fs::remove_file(root_dir.join("first.zip")).unwrap();
+
+    let (status, _) = send(
+        app.clone(),
+        request(Method::POST, "/api/admin/reload", None, &[]),
+    )
+    .await;
+    assert_eq!(status, 401, "Reload requires the admin token");
+
+    let headers = [("X-Access-Token", "admin-token")];
+    let request = request(Method::POST, "/api/admin/reload", None, &headers);
+    let (status, _) = send(app.clone(), request).await;
+    assert_eq!(status, 401, "Admin tokens are only read from X-Admin-Token");
+
+    let (status, body) = admin(app.clone(), Method::POST, "/api/admin/reload/third", None).await;
+    assert_eq!(status, 200, "Response should be 200.");
+    assert_eq!(body, json!([{ "change": "created", "project": "third" }]));
+
+    let (status, body) = admin(app.clone(), Method::POST, "/api/admin/reload", None).await;
+    let _ = fs::remove_dir_all(&root_dir);
+
+    assert_eq!(status, 200, "Response should be 200.");
+    assert_eq!(
+        body,
+        json!([
+            { "change": "removed", "project": "first" },
+            { "change": "created", "project": "second" }
+        ])
+    );
+}
+
+#[tokio::test]
+async fn admin_reload_disabled() {
+    let app = admin_app(Path::new("tests/data"), &[]).await;
+
+    let (status, _) = admin(app, Method::POST, "/api/admin/reload", None).await;
+    assert_eq!(status, 403, "Admin API is disabled without tokens");
+}
+
+async fn served_release(app: Router) -> Value {
+    let (status, body) = evaluate(
+        app,
+        "release-project",
+        "sample-small",
+        json!({ "context": {} }),
+        &[("X-Access-Token", "release-token")],
+    )
+    .await;
+    assert_eq!(status, 200, "Response should be 200.");
+
+    body["details"]["releaseId"].clone()
+}
+
+#[tokio::test]
+async fn admin_pin_release() {
+    let root_dir = temp_dir("admin-pin");
+    let project_zip = root_dir.join("release-project.zip");
+    fs::copy("tests/data-releases/release-1.zip", &project_zip).unwrap();
+
+    let app = admin_app(&root_dir, &["admin-token"]).await;
+    let pin = "/api/admin/projects/release-project/pin";
+
+    fs::copy("tests/data-releases/release-2.zip", &project_zip).unwrap();
+    admin(app.clone(), Method::POST, "/api/admin/reload", None).await;
+    assert_eq!(served_release(app.clone()).await, "release-2");
+
+    let release_id = json!({ "releaseId": "release-unknown" });
+    let (status, _) = admin(app.clone(), Method::PUT, pin, Some(release_id)).await;
+    assert_eq!(status, 404, "Only retained releases can be pinned");
+
+    let release_id = json!({ "releaseId": "release-1" });
+    let (status, body) = admin(app.clone(), Method::PUT, pin, Some(release_id)).await;
+    assert_eq!(status, 200, "Response should be 200.");
+    assert_eq!(body["pinnedReleaseId"], "release-1");
+    assert_eq!(body["releases"][0]["releaseId"], "release-2");
+    assert_eq!(body["releases"][1]["active"], true);
+    assert_eq!(served_release(app.clone()).await, "release-1");
+
+    // Polls that still see the bad release keep serving the pinned one
+    admin(app.clone(), Method::POST, "/api/admin/reload", None).await;
+    assert_eq!(served_release(app.clone()).await, "release-1");
+
+    let (status, body) = admin(app.clone(), Method::DELETE, pin, None).await;
+    let _ = fs::remove_dir_all(&root_dir);
+
+    assert_eq!(status, 200, "Response should be 200.");
+    assert_eq!(body["pinnedReleaseId"], Value::Null);
+    assert_eq!(served_release(app).await, "release-2");
+}
+
+#[tokio::test]
+async fn evaluate_release_id() {
+    let root_dir = temp_dir("release-id");
+    let project_zip = root_dir.join("release-project.zip");
+    fs::copy("tests/data-releases/release-1.zip", &project_zip).unwrap();
+
+    let app = admin_app(&root_dir, &["admin-token"]).await;
+
+    fs::copy("tests/data-releases/release-2.zip", &project_zip).unwrap();
+    admin(app.clone(), Method::POST, "/api/admin/reload", None).await;
+    let _ = fs::remove_dir_all(&root_dir);
+
+    let token = ("X-Access-Token", "release-token");
+    let context = json!({ "context": {} });
+    let project = "release-project";
+
+    let headers = [token, ("X-Release-Id", "release-1")];
+    let (status, body) = evaluate(
+        app.clone(),
+        project,
+        "sample-small",
+        context.clone(),
+        &headers,
+    )
+    .await;
+    assert_eq!(status, 200, "Retained releases can be requested");
+    assert_eq!(body["details"]["releaseId"], "release-1");
+
+    let release_2 = json!({ "context": {}, "releaseId": "release-2" });
+    let (status, body) = evaluate(
+        app.clone(),
+        project,
+        "sample-small",
+        release_2.clone(),
+        &[token],
+    )
+    .await;
+    assert_eq!(status, 200, "Response should be 200.");
+    assert_eq!(body["details"]["releaseId"], "release-2");
+
+    let headers = [token, ("X-Release-Id", "release-3")];
+    let (status, _) = evaluate(
+        app.clone(),
+        project,
+        "sample-small",
+        context.clone(),
+        &headers,
+    )
+    .await;
+    assert_eq!(status, 409, "Unknown releases conflict");
+
+    let headers = [token, ("X-Release-Id", "release-1")];
+    let (status, _) = evaluate(app.clone(), project, "sample-small", release_2, &headers).await;
+    assert_eq!(status, 400, "Header and body must agree");
+
+    let token = ("X-Access-Token", "release-2-token");
+    let headers = [token, ("X-Release-Id", "release-2")];
+    let (status, _) = evaluate(
+        app.clone(),
+        project,
+        "sample-small",
+        context.clone(),
+        &headers,
+    )
+    .await;
+    assert_eq!(status, 200, "Response should be 200.");
+    let headers = [token, ("X-Release-Id", "release-1")];
+    let (status, _) = evaluate(app, project, "sample-small", context, &headers).await;
+    assert_eq!(
+        status, 401,
+        "Tokens are checked against the requested release"
+    );
+}
@@ -1,8 +1,19 @@
+use crate::support::azurite::AzuriteContainer;
 use crate::support::minio::MinioContainer;
 use crate::support::path::ValidateProject;
+use agent::RefreshScope;
 use agent::app;
-use agent::config::{EnvironmentConfig, ProviderConfig, S3ProviderConfig, ZipProviderConfig};
-use std::env;
+use agent::config::{
+    AzureStorageProviderConfig, EnvironmentConfig, FilesystemProviderConfig, ProviderConfig,
+    S3ProviderConfig, ZipProviderConfig,
+};
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+use std::time::Duration;
+use std::{env, fs};
+use zip::ZipArchive;
 
 mod support;
 
@@ -32,6 +43,195 @@ async fn zip_agent() {
     );
 }
 
+#[tokio::test]
+async fn zip_agent_reload() {
+    let root_dir = env::temp_dir().join(format!("agent-zip-reload-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root_dir);
+    fs::create_dir_all(&root_dir).unwrap();
+    fs::copy("tests/data/sample-project.zip", root_dir.join("first.zip")).unwrap();
+
+    let config = EnvironmentConfig {
+        provider: ProviderConfig::Zip(ZipProviderConfig {
+            root_dir: root_dir.to_str().unwrap().to_string(),
+        }),
+        poll_interval: Duration::from_millis(1_000),
+        ..Default::default()
+    };
+
+    let agent = app::create_agent(config, Default::default()).await;
+    assert!(agent.project("first").is_some(), "first was not found");
+
+    fs::copy("tests/data/sample-project.zip", root_dir.join("second.zip")).unwrap();
+    fs::remove_file(root_dir.join("first.zip")).unwrap();
+
+    let reloaded =
+        wait_for(|| agent.project("second").is_some() && agent.project("first").is_none()).await;
+    let _ = fs::remove_dir_all(&root_dir);
+
+    assert!(reloaded, "zip directory changes were not picked up");
+}
+
+#[tokio::test]
+async fn zip_agent_missing_root() {
+    let root_dir = env::temp_dir().join(format!("agent-zip-missing-{}", std::process::id()));
+    let moved_dir = root_dir.with_extension("moved");
+    let _ = fs::remove_dir_all(&root_dir);
+    let _ = fs::remove_dir_all(&moved_dir);
+
+    let config = EnvironmentConfig {
+        provider: ProviderConfig::Zip(ZipProviderConfig {
+            root_dir: root_dir.to_str().unwrap().to_string(),
+        }),
+        ..Default::default()
+    };
+
+    let agent = app::create_agent(config, Default::default()).await;
+    assert_eq!(agent.project_count(), 0, "Missing root starts empty");
+
+    fs::create_dir_all(&root_dir).unwrap();
+    fs::copy("tests/data/sample-project.zip", root_dir.join("first.zip")).unwrap();
+    agent.refresh_data(RefreshScope::All).await.unwrap();
+    assert!(agent.project("first").is_some(), "first was not found");
+
+    fs::rename(&root_dir, &moved_dir).unwrap();
+    let refreshed = agent.refresh_data(RefreshScope::All).await;
+    let _ = fs::remove_dir_all(&moved_dir);
+
+    assert!(refreshed.is_err(), "Refresh fails once the root is gone");
+    assert!(
+        agent.project("first").is_some(),
+        "Projects stay loaded while the root is missing"
+    );
+}
+
+fn sample_decision() -> Vec<u8> {
+    let mut archive =
+        ZipArchive::new(File::open("tests/data/sample-project.zip").unwrap()).unwrap();
+    let mut decision = Vec::new();
+    archive
+        .by_name("sample-small")
+        .unwrap()
+        .read_to_end(&mut decision)
+        .unwrap();
+    decision
+}
+
+/// Lays out `files` the way Kubernetes mounts a ConfigMap, as symlinks into `..data`.
+fn mount_volume(directory: &Path, files: &[(&str, Vec<u8>)]) {
+    let data_dir = directory.join("..2026_10_17_12_00_00.000000001");
+    fs::create_dir_all(&data_dir).unwrap();
+    for (name, content) in files {
+        fs::write(data_dir.join(name), content).unwrap();
+        symlink(Path::new("..data").join(name), directory.join(name)).unwrap();
+    }
+    symlink(data_dir.file_name().unwrap(), directory.join("..data")).unwrap();
+}
+
+#[tokio::test]
+async fn zip_agent_mounted_volume() {
+    let root_dir = env::temp_dir().join(format!("agent-zip-volume-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root_dir);
+    let content = fs::read("tests/data/sample-project.zip").unwrap();
+    mount_volume(&root_dir, &[("first.zip", content)]);
+
+    let config = EnvironmentConfig {
+        provider: ProviderConfig::Zip(ZipProviderConfig {
+            root_dir: root_dir.to_str().unwrap().to_string(),
+        }),
+        ..Default::default()
+    };
+
+    let agent = app::create_agent(config, Default::default()).await;
+    assert!(agent.project("first").is_some(), "first was not found");
+    assert_eq!(agent.project_count(), 1, "Volume metadata is not a project");
+
+    symlink("..data/missing.zip", root_dir.join("second.zip")).unwrap();
+    let refreshed = agent.refresh_data(RefreshScope::All).await;
+    let _ = fs::remove_dir_all(&root_dir);
+
+    assert!(refreshed.is_err(), "Refresh fails on an unreadable zip");
+    assert!(
+        agent.project("first").is_some(),
+        "Projects stay loaded when the refresh fails"
+    );
+}
+
+#[tokio::test]
+async fn filesystem_agent_mounted_volume() {
+    let root_dir = env::temp_dir().join(format!("agent-fs-volume-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root_dir);
+    mount_volume(
+        &root_dir.join("volume-project"),
+        &[("sample-small", sample_decision())],
+    );
+
+    let config = EnvironmentConfig {
+        provider: ProviderConfig::Filesystem(FilesystemProviderConfig {
+            root_dir: root_dir.to_str().unwrap().to_string(),
+        }),
+        ..Default::default()
+    };
+
+    let agent = app::create_agent(config, Default::default()).await;
+    let project = agent.project("volume-project");
+    let _ = fs::remove_dir_all(&root_dir);
+
+    let project = project.expect("volume-project was not found");
+    assert!(project.engine.get_decision("sample-small").await.is_ok());
+    assert!(
+        project
+            .engine
+            .get_decision("..data/sample-small")
+            .await
+            .is_err(),
+        "Volume metadata is not loaded"
+    );
+}
+
+#[tokio::test]
+async fn filesystem_agent_reload() {
+    let root_dir = env::temp_dir().join(format!("agent-fs-reload-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root_dir);
+    let project_dir = root_dir.join("reload-project");
+    fs::create_dir_all(&project_dir).unwrap();
+    let decision = sample_decision();
+    fs::write(project_dir.join("sample-small"), &decision).unwrap();
+
+    let config = EnvironmentConfig {
+        provider: ProviderConfig::Filesystem(FilesystemProviderConfig {
+            root_dir: root_dir.to_str().unwrap().to_string(),
+        }),
+        ..Default::default()
+    };
+
+    let agent = app::create_agent(config, Default::default()).await;
+    let loaded = agent.project("reload-project").unwrap();
+    let unchanged = agent.refresh_data(RefreshScope::All).await.unwrap();
+
+    fs::write(project_dir.join("copy of sample-small"), &decision).unwrap();
+    let changed = agent.refresh_data(RefreshScope::All).await.unwrap();
+    let reloaded = agent.project("reload-project").unwrap();
+    let _ = fs::remove_dir_all(&root_dir);
+
+    assert!(unchanged.is_empty(), "Unchanged files do not reload");
+    assert_eq!(changed.len(), 1, "Added file reloads the project");
+    assert_ne!(reloaded.content_hash, loaded.content_hash);
+    let decision = reloaded.engine.get_decision("copy of sample-small").await;
+    assert!(decision.is_ok(), "Reloaded project has the added file");
+}
+
+async fn wait_for(condition: impl Fn() -> bool) -> bool {
+    for _ in 0..50 {
+        if condition() {
+            return true;
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    false
+}
+
 #[tokio::test]
 async fn s3_agent() {
     let minio = MinioContainer::start()
@@ -115,3 +315,70 @@ async fn s3_agent_prefix() {
         .expect("nested-project was not found");
     nested_project.validate_project().await;
 }
+
+#[tokio::test]
+async fn s3_agent_paginated() {
+    let minio = MinioContainer::start()
+        .await
+        .expect("Minio container is available");
+    minio
+        .put_copies("bulk", 1_050)
+        .await
+        .expect("Bulk objects are uploaded");
+    let host_port = minio
+        .container
+        .get_host_port_ipv4(9000)
+        .await
+        .expect("Minio port 9000 is available");
+    let minio_image = minio.container.image();
+
+    unsafe { env::set_var("AWS_ACCESS_KEY_ID", minio_image.username.clone()) };
+    unsafe { env::set_var("AWS_SECRET_ACCESS_KEY", minio_image.password.clone()) };
+
+    let config = EnvironmentConfig {
+        provider: ProviderConfig::S3(S3ProviderConfig {
+            bucket: minio_image.bucket_name.to_string(),
+            prefix: Some("bulk".to_string()),
+            endpoint: Some(minio_image.endpoint(host_port)),
+            force_path_style: true,
+        }),
+        ..Default::default()
+    };
+
+    let agent = app::create_agent(config, Default::default()).await;
+    for i in [0, 999, 1_000, 1_049] {
+        assert!(
+            agent.project(&format!("project-{i}")).is_some(),
+            "project-{i} was not found"
+        );
+    }
+}
+
+#[tokio::test]
+async fn azure_agent_paginated() {
+    let azurite = AzuriteContainer::start()
+        .await
+        .expect("Azurite container is available");
+    azurite
+        .put_copies("bulk", 1_050)
+        .await
+        .expect("Bulk blobs are uploaded");
+
+    let config = EnvironmentConfig {
+        provider: ProviderConfig::AzureStorage(AzureStorageProviderConfig {
+            connection_string: Some(azurite.connection_string.clone()),
+            account_name: None,
+            container: azurite.container_name.clone(),
+            prefix: Some("bulk".to_string()),
+        }),
+        ..Default::default()
+    };
+
+    let agent = app::create_agent(config, Default::default()).await;
+    for i in [0, 999, 1_000, 1_049] {
+        assert!(
+            agent.project(&format!("project-{i}")).is_some(),
+            "project-{i} was not found"
+        );
+    }
+}
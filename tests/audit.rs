@@ -0,0 +1,180 @@
+mod support;
+
+use agent::config::{
+    AuditConfig, AuditSinkConfig, EnvironmentConfig, FileAuditSinkConfig, WebhookAuditSinkConfig,
+};
+use axum::Router;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use support::{evaluate, temp_dir, zip_app};
+use tokio::net::TcpListener;
+
+const TOKEN: [(&str, &str); 1] = [("X-Access-Token", "secured-token")];
+
+fn audited(sink: AuditSinkConfig) -> EnvironmentConfig {
+    EnvironmentConfig {
+        audit: Some(AuditConfig { sink, buffer: 100 }),
+        ..Default::default()
+    }
+}
+
+fn read_records(path: &Path) -> Vec<Value> {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn audit_file_sink() {
+    let path = temp_dir("audit-file").join("audit.jsonl");
+    let (agent, app) = zip_app(
+        "tests/data-secured",
+        audited(AuditSinkConfig::File(FileAuditSinkConfig {
+            path: path.clone(),
+            max_bytes: 1024 * 1024,
+            max_files: 2,
+        })),
+    )
+    .await;
+
+    let context = json!({ "applicant": { "id": 7 } });
+    let body = json!({ "context": context });
+    let (status, _) = evaluate(app.clone(), "secured-project", "sample-small", body, &TOKEN).await;
+    assert_eq!(status, StatusCode::OK);
+    let body = json!({ "context": {} });
+    let (status, _) = evaluate(app.clone(), "secured-project", "missing", body, &TOKEN).await;
+    assert_ne!(status, StatusCode::OK);
+
+    let project_id = "7f1c2a9e-3a51-4c1e-9a5b-1d2f0c8e4b11";
+    let body = json!({ "context": {} });
+    let (status, _) = evaluate(app, project_id, "sample-small", body, &TOKEN).await;
+    assert_eq!(status, StatusCode::OK);
+    agent.close_audit(Duration::from_secs(5)).await;
+
+    let records = read_records(&path);
+    assert_eq!(records.len(), 3);
+
+    let record = &records[0];
+    assert_eq!(record["project"], "secured-project");
+    assert_eq!(record["decision"], "sample-small");
+    assert_eq!(record["releaseId"], "0b6d3c52-5f7e-4f55-8d0e-2c9a1e7f6a22");
+    assert!(record["caller"].as_str().unwrap().starts_with("token:"));
+    assert!(!record["caller"].as_str().unwrap().contains("secured-token"));
+    assert_eq!(record["context"]["applicant"]["id"], 7);
+    assert_eq!(record["output"]["applicant"]["id"], 7);
+    assert!(record["latencyMs"].as_f64().unwrap() >= 0.0);
+    assert!(record["timestamp"].is_string());
+    assert!(record["error"].is_null());
+
+    assert_eq!(records[1]["decision"], "missing");
+    assert!(records[1]["output"].is_null());
+    assert!(!records[1]["error"].is_null());
+
+    // Projects are recorded by their key, however the caller addressed them
+    assert_eq!(records[2]["project"], "secured-project");
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+}
+
+#[tokio::test]
+async fn audit_file_rotation() {
+    let path = temp_dir("audit-rotation").join("audit.jsonl");
+    let (agent, app) = zip_app(
+        "tests/data-secured",
+        audited(AuditSinkConfig::File(FileAuditSinkConfig {
+            path: path.clone(),
+            max_bytes: 64,
+            max_files: 2,
+        })),
+    )
+    .await;
+
+    for _ in 0..5 {
+        let body = json!({ "context": {} });
+        let (status, _) =
+            evaluate(app.clone(), "secured-project", "sample-small", body, &TOKEN).await;
+        assert_eq!(status, StatusCode::OK);
+        // Lets the writer catch up, so every record lands in a batch of its own
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    agent.close_audit(Duration::from_secs(5)).await;
+
+    let rotated = |index: usize| PathBuf::from(format!("{}.{index}", path.display()));
+    assert_eq!(read_records(&path).len(), 1);
+    assert_eq!(read_records(&rotated(1)).len(), 1);
+    assert_eq!(read_records(&rotated(2)).len(), 1);
+    assert!(!rotated(3).exists());
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+}
+
+struct Delivery {
+    authorization: Option<String>,
+    body: String,
+}
+
+#[derive(Clone, Default)]
+struct Received {
+    deliveries: Arc<Mutex<Vec<Delivery>>>,
+}
+
+async fn receive(State(received): State<Received>, headers: HeaderMap, body: String) {
+    let authorization = headers
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string);
+    let delivery = Delivery {
+        authorization,
+        body,
+    };
+    received.deliveries.lock().unwrap().push(delivery);
+}
+
+#[tokio::test]
+async fn audit_webhook_sink() {
+    let received = Received::default();
+    let webhook = Router::new()
+        .route("/audit", post(receive))
+        .with_state(received.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, webhook).await });
+
+    let (agent, app) = zip_app(
+        "tests/data-secured",
+        audited(AuditSinkConfig::Webhook(WebhookAuditSinkConfig {
+            url: format!("http://{address}/audit"),
+            token: Some("webhook-token".to_string()),
+            timeout: Duration::from_secs(5),
+        })),
+    )
+    .await;
+
+    for _ in 0..3 {
+        let body = json!({ "context": {} });
+        let (status, _) =
+            evaluate(app.clone(), "secured-project", "sample-small", body, &TOKEN).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    agent.close_audit(Duration::from_secs(5)).await;
+
+    let deliveries = received.deliveries.lock().unwrap();
+    assert!(!deliveries.is_empty());
+    for delivery in deliveries.iter() {
+        let authorization = delivery.authorization.as_deref();
+        assert_eq!(authorization, Some("Bearer webhook-token"));
+    }
+
+    let records = deliveries
+        .iter()
+        .flat_map(|delivery| delivery.body.lines())
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(records.len(), 3);
+    assert!(records.iter().all(|r| r["decision"] == "sample-small"));
+}
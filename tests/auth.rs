@@ -0,0 +1,297 @@
+mod support;
+
+use agent::RefreshScope;
+use agent::config::{EnvironmentConfig, JwtConfig};
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use serde_json::{Value, json};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use support::{evaluate, evaluate_request, request, send, temp_dir, zip_app};
+
+async fn jwt_app() -> Router {
+    let config = EnvironmentConfig {
+        jwt: Some(JwtConfig {
+            jwks_file: Some("tests/data-jwt/jwks.json".into()),
+            issuer: Some("https://issuer.test".to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    zip_app("tests/data-secured", config).await.1
+}
+
+fn sign(claims: Value) -> String {
+    let key = EncodingKey::from_ed_pem(include_bytes!("data-jwt/private.pem")).unwrap();
+    let mut header = Header::new(Algorithm::EdDSA);
+    header.kid = Some("test-key".to_string());
+
+    encode(&header, &claims, &key).unwrap()
+}
+
+fn claims(extra: Value) -> Value {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let mut claims = json!({ "iss": "https://issuer.test", "exp": now + 300 });
+    claims
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    claims
+}
+
+async fn evaluate_with_jwt(app: Router, key: &str, token: &str) -> StatusCode {
+    let bearer = format!("Bearer {token}");
+    let headers = [("Authorization", bearer.as_str())];
+    let context = json!({ "context": {} });
+    evaluate(app, "secured-project", key, context, &headers)
+        .await
+        .0
+}
+
+#[tokio::test]
+async fn jwt_project_access() {
+    let app = jwt_app().await;
+
+    let token = sign(claims(json!({ "projects": ["secured-project"] })));
+    assert_eq!(
+        evaluate_with_jwt(app.clone(), "sample-small", &token).await,
+        200
+    );
+
+    let token = sign(claims(json!({ "projects": "other-project" })));
+    assert_eq!(
+        evaluate_with_jwt(app.clone(), "sample-small", &token).await,
+        401,
+        "Token does not grant the project"
+    );
+
+    let token = sign(claims(json!({
+        "projects": ["*"],
+        "iss": "https://other-issuer.test"
+    })));
+    assert_eq!(
+        evaluate_with_jwt(app.clone(), "sample-small", &token).await,
+        401,
+        "Issuer must match"
+    );
+
+    let token = sign(claims(json!({ "projects": ["*"], "exp": 1 })));
+    assert_eq!(
+        evaluate_with_jwt(app, "sample-small", &token).await,
+        401,
+        "Expired tokens are rejected"
+    );
+}
+
+#[tokio::test]
+async fn jwt_decision_access() {
+    let app = jwt_app().await;
+    let token = sign(claims(json!({
+        "projects": ["secured-project"],
+        "decisions": ["copy of sample-small"]
+    })));
+
+    assert_eq!(
+        evaluate_with_jwt(app.clone(), "copy of sample-small", &token).await,
+        200
+    );
+    assert_eq!(
+        evaluate_with_jwt(app.clone(), "sample-small", &token).await,
+        403,
+        "Token does not grant the decision"
+    );
+
+    let bearer = format!("Bearer {token}");
+    let uri = "/api/projects/secured-project/entrypoints";
+    let request = request(
+        Method::GET,
+        uri,
+        None,
+        &[("Authorization", bearer.as_str())],
+    );
+    let (status, body) = send(app, request).await;
+    assert_eq!(status, 200, "Response should be 200.");
+    assert_eq!(
+        body["entrypoints"],
+        json!([{ "path": "copy of sample-small", "type": "graph" }])
+    );
+}
+
+async fn status(app: Router, request: Request<Body>) -> StatusCode {
+    send(app, request).await.0
+}
+
+fn evaluate_as(project: &str, key: &str, body: Value, token: &str) -> Request<Body> {
+    evaluate_request(project, key, body, &[("X-Access-Token", token)])
+}
+
+#[tokio::test]
+async fn hashed_access_tokens() {
+    let config = EnvironmentConfig {
+        require_metadata_auth: true,
+        ..Default::default()
+    };
+    let (_, app) = zip_app("tests/data-hashed", config).await;
+    let evaluate = |token| {
+        evaluate_as(
+            "hashed-project",
+            "sample-small",
+            json!({ "context": {} }),
+            token,
+        )
+    };
+
+    assert_eq!(
+        status(app.clone(), evaluate("sha256-token")).await,
+        200,
+        "Salted SHA-256 token"
+    );
+    assert_eq!(
+        status(app.clone(), evaluate("argon2-token")).await,
+        200,
+        "Argon2 token"
+    );
+    assert_eq!(
+        status(app.clone(), evaluate("argon2-token")).await,
+        200,
+        "Cached argon2 token"
+    );
+    assert_eq!(
+        status(app.clone(), evaluate("plain-token")).await,
+        200,
+        "Plaintext token"
+    );
+    assert_eq!(
+        status(app.clone(), evaluate("pepper")).await,
+        401,
+        "Salt is not a token"
+    );
+    assert_eq!(
+        status(app.clone(), evaluate("pepper")).await,
+        401,
+        "Rejected token"
+    );
+    assert_eq!(
+        status(app.clone(), evaluate("$argon2id$v=19$m=19456,t=2,p=1$YWdlbnQtdGVzdC1zYWx0MQ$7TbJzJnvKo56avRzSpjnwhRy4u0X3QiazU/fijz74MU")).await,
+        401,
+        "Stored hashes are not tokens"
+    );
+
+    let metadata = |token| request(Method::GET, "/api.json", None, &[("X-Access-Token", token)]);
+    assert_eq!(
+        status(app.clone(), metadata("argon2-token")).await,
+        200,
+        "Argon2 token"
+    );
+    assert_eq!(
+        status(app.clone(), metadata("sha256-token")).await,
+        200,
+        "Salted SHA-256 token"
+    );
+    assert_eq!(status(app, metadata("pepper")).await, 401);
+}
+
+#[tokio::test]
+async fn scoped_access_tokens() {
+    let (_, app) = zip_app("tests/data-scoped", Default::default()).await;
+
+    let plain = json!({ "context": {} });
+    let traced = json!({ "context": {}, "trace": true });
+    let evaluate =
+        |key, body: &Value, token| evaluate_as("scoped-project", key, body.clone(), token);
+    let entrypoints = |token| {
+        let uri = "/api/projects/scoped-project/entrypoints";
+        request(Method::GET, uri, None, &[("X-Access-Token", token)])
+    };
+
+    assert_eq!(
+        status(app.clone(), evaluate("sample-small", &traced, "full-token")).await,
+        200
+    );
+    assert_eq!(status(app.clone(), entrypoints("full-token")).await, 200);
+
+    assert_eq!(
+        status(
+            app.clone(),
+            evaluate("copy of sample-small", &plain, "scoped-token")
+        )
+        .await,
+        200
+    );
+    assert_eq!(
+        status(
+            app.clone(),
+            evaluate("sample-small", &plain, "scoped-token")
+        )
+        .await,
+        403,
+        "Decision is outside the token scope"
+    );
+    assert_eq!(
+        status(
+            app.clone(),
+            evaluate("copy of sample-small", &traced, "scoped-token")
+        )
+        .await,
+        403,
+        "Token does not allow trace"
+    );
+    assert_eq!(
+        status(app, entrypoints("scoped-token")).await,
+        403,
+        "Token does not allow listing entrypoints"
+    );
+}
+
+#[tokio::test]
+async fn invalid_release_data() {
+    let root_dir = temp_dir("invalid-scope");
+    let project_zip = root_dir.join("scoped-project.zip");
+    fs::copy("tests/data-scoped/scoped-project.zip", &project_zip).unwrap();
+
+    let (agent, app) = zip_app(root_dir.to_str().unwrap(), Default::default()).await;
+    let evaluate = |headers: &[(&'static str, &str)]| {
+        evaluate_request(
+            "scoped-project",
+            "sample-small",
+            json!({ "context": {} }),
+            headers,
+        )
+    };
+
+    assert_eq!(status(app.clone(), evaluate(&[])).await, 401);
+
+    // The scoped token lists its decisions as a string, which does not parse
+    fs::copy("tests/data-invalid/scoped-project.zip", &project_zip).unwrap();
+    agent.refresh_data(RefreshScope::All).await.unwrap();
+
+    assert_eq!(
+        status(app.clone(), evaluate(&[])).await,
+        401,
+        "Broken release data must not make the project public"
+    );
+    assert_eq!(
+        status(app.clone(), evaluate(&[("X-Access-Token", "full-token")])).await,
+        200,
+        "Previous release stays in place"
+    );
+
+    fs::remove_file(&project_zip).unwrap();
+    agent.refresh_data(RefreshScope::All).await.unwrap();
+    fs::copy("tests/data-invalid/scoped-project.zip", &project_zip).unwrap();
+    agent.refresh_data(RefreshScope::All).await.unwrap();
+    let _ = fs::remove_dir_all(&root_dir);
+
+    assert_eq!(
+        status(app, evaluate(&[])).await,
+        404,
+        "Project with broken release data is not served"
+    );
+}
@@ -1,13 +1,21 @@
 use crate::support::minio::MinioContainer;
 use crate::support::path::decision_paths;
+use crate::support::{evaluate, request, send, temp_dir, zip_app};
 use agent::app;
-use agent::config::{EnvironmentConfig, ProviderConfig, S3ProviderConfig};
+use agent::config::{
+    AdminConfig, EnvironmentConfig, ProviderConfig, S3ProviderConfig, TracePolicy,
+};
+use axum::Router;
 use axum::body::{Body, to_bytes};
-use axum::http::{Request, Response};
+use axum::http::{Method, Request, Response, StatusCode};
 use serde::Deserialize;
-use serde_json::json;
-use std::env;
+use serde_json::{Value, json};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use std::{env, fs};
 use tower::ServiceExt;
+use zen_engine::ZEN_CONFIG;
 
 mod support;
 
@@ -67,6 +75,621 @@ async fn s3_engine_prefix() {
     run_engine_test(config, "nested-project").await;
 }
 
+#[tokio::test]
+async fn zip_engine_batch() {
+    let (_, app) = zip_app("tests/data", Default::default()).await;
+
+    let body = json!({
+        "items": [
+            { "context": { "hello": "first" } },
+            { "key": "first level/nested-sample", "context": { "hello": "second" } },
+            { "key": "missing", "context": { "hello": "third" } },
+        ]
+    });
+    let uri = "/api/projects/sample-project/evaluate-batch/sample-small";
+    let (status, body) = send(app, request(Method::POST, uri, Some(body), &[])).await;
+    assert_eq!(status, 200, "Response should be 200.");
+
+    let results = body["results"].as_array().expect("results is an array");
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["key"], "sample-small");
+    assert_eq!(results[0]["result"]["hello"], "first");
+    assert_eq!(results[1]["key"], "first level/nested-sample");
+    assert_eq!(results[1]["result"]["hello"], "second");
+    assert_eq!(results[2]["key"], "missing");
+    assert!(
+        results[2]["error"].is_object(),
+        "Missing key reports an error"
+    );
+}
+
+#[tokio::test]
+async fn zip_engine_batch_too_large() {
+    let (_, app) = zip_app("tests/data", Default::default()).await;
+
+    let items = vec![json!({ "context": {} }); 1_001];
+    let body = json!({ "items": items });
+    let uri = "/api/projects/sample-project/evaluate-batch/sample-small";
+    let (status, _) = send(app, request(Method::POST, uri, Some(body), &[])).await;
+    assert_eq!(status, 413, "Batches are limited to 1000 items.");
+}
+
+/// Polls `/metrics` until it contains every expected line or 5 seconds have passed.
+async fn wait_for_metrics(app: Router, expected: &[&str]) -> String {
+    let start = Instant::now();
+    loop {
+        let request = Request::get("/metrics").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let metrics = String::from_utf8(body.to_vec()).unwrap();
+
+        if expected.iter().all(|e| metrics.contains(e)) || start.elapsed() > Duration::from_secs(5)
+        {
+            return metrics;
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn zip_engine_shadow() {
+    let config = EnvironmentConfig {
+        shadow_concurrency: Some(3),
+        ..Default::default()
+    };
+    let (_, app) = zip_app("tests/data-shadow", config).await;
+
+    for key in ["sample-small", "changed", "copy of sample-small"] {
+        let context = json!({ "context": { "hello": "world" } });
+        let (status, body) = evaluate(app.clone(), "shadow-project", key, context, &[]).await;
+        assert_eq!(status, 200, "Response should be 200.");
+        assert_eq!(
+            body["result"],
+            json!({ "hello": "world" }),
+            "Only the primary result is returned"
+        );
+    }
+
+    let expected = [
+        r#"decision="sample-small",outcome="match""#,
+        r#"decision="changed",outcome="mismatch""#,
+        r#"decision="copy of sample-small",outcome="error""#,
+    ];
+    let metrics = wait_for_metrics(app, &expected).await;
+    for e in expected {
+        assert!(metrics.contains(e), "Shadow outcome {e} is recorded");
+    }
+}
+
+#[tokio::test]
+async fn zip_engine_shadow_saturated() {
+    let config = EnvironmentConfig {
+        shadow_concurrency: Some(0),
+        ..Default::default()
+    };
+    let (_, app) = zip_app("tests/data-shadow", config).await;
+
+    let context = json!({ "context": { "hello": "world" } });
+    let (status, _) = evaluate(app.clone(), "shadow-project", "changed", context, &[]).await;
+    assert_eq!(status, 200, "Response should be 200.");
+
+    let skipped = r#"decision="changed",outcome="skipped""#;
+    assert!(
+        wait_for_metrics(app, &[skipped]).await.contains(skipped),
+        "Shadow evaluations are skipped without a permit"
+    );
+}
+
+#[tokio::test]
+async fn zip_engine_canary() {
+    let (_, app) = zip_app("tests/data-canary", Default::default()).await;
+
+    let details = async |applicant_id: u32, sticky_header: Option<&str>| {
+        let context = json!({ "context": { "applicant": { "id": applicant_id } } });
+        let mut headers = vec![("X-Access-Token", "canary-token")];
+        headers.extend(sticky_header.map(|header| ("X-Applicant-Id", header)));
+
+        let (status, body) = evaluate(
+            app.clone(),
+            "canary-project",
+            "sample-small",
+            context,
+            &headers,
+        )
+        .await;
+        assert_eq!(status, 200, "Response should be 200.");
+        body["details"].clone()
+    };
+
+    let mut canary_count = 0;
+    for applicant_id in 0..100 {
+        let first = details(applicant_id, None).await;
+        let is_canary = first["canary"] == true;
+        let expected_release = if is_canary {
+            "release-canary"
+        } else {
+            "release-primary"
+        };
+
+        assert_eq!(first["releaseId"], expected_release);
+        assert_eq!(
+            details(applicant_id, None).await,
+            first,
+            "Routing is sticky per applicant"
+        );
+        canary_count += is_canary as u32;
+    }
+
+    assert!(
+        (25..=75).contains(&canary_count),
+        "Roughly half of applicants hit the canary ({canary_count})"
+    );
+
+    let by_header = details(0, Some("applicant-a")).await;
+    for applicant_id in 1..10 {
+        assert_eq!(
+            details(applicant_id, Some("applicant-a")).await,
+            by_header,
+            "Sticky header takes precedence over the context field"
+        );
+    }
+}
+
+#[tokio::test]
+async fn zip_engine_canary_not_shadowed() {
+    let root_dir = temp_dir("canary-shadow");
+    for file in ["canary-project.zip", "canary-project.canary.zip"] {
+        fs::copy(
+            Path::new("tests/data-canary").join(file),
+            root_dir.join(file),
+        )
+        .unwrap();
+    }
+    fs::copy(
+        "tests/data-canary/canary-project.zip",
+        root_dir.join("canary-project.shadow.zip"),
+    )
+    .unwrap();
+
+    let config = EnvironmentConfig {
+        shadow_concurrency: Some(20),
+        ..Default::default()
+    };
+    let (_, app) = zip_app(root_dir.to_str().unwrap(), config).await;
+    let _ = fs::remove_dir_all(&root_dir);
+
+    let token = [("X-Access-Token", "canary-token")];
+    let mut primary_count = 0;
+    for applicant_id in 0..20 {
+        let context = json!({ "context": { "applicant": { "id": applicant_id } } });
+        let (status, body) = evaluate(
+            app.clone(),
+            "canary-project",
+            "sample-small",
+            context,
+            &token,
+        )
+        .await;
+        assert_eq!(status, 200, "Response should be 200.");
+        primary_count += (body["details"]["canary"] != true) as u32;
+    }
+    assert!((1..20).contains(&primary_count), "Both releases answered");
+
+    // Only evaluations answered by the primary release are compared with the candidate
+    let shadowed = format!(
+        r#"project="canary-project",decision="sample-small",outcome="match"}} {primary_count}"#
+    );
+    let metrics = wait_for_metrics(app, &[&shadowed]).await;
+    assert!(
+        metrics.contains(&shadowed),
+        "{primary_count} evaluations are shadowed"
+    );
+}
+
+#[tokio::test]
+async fn zip_engine_canary_access() {
+    let (agent, app) = zip_app("tests/data-canary-scoped", Default::default()).await;
+    assert_eq!(agent.project_count(), 2, "Canary releases are not counted");
+
+    let shared = [("X-Access-Token", "shared-token")];
+    let primary = [("X-Access-Token", "primary-token")];
+    let context = json!({ "context": {} });
+
+    let (_, body) = evaluate(
+        app.clone(),
+        "scoped-canary",
+        "sample-small",
+        context.clone(),
+        &shared,
+    )
+    .await;
+    assert_eq!(body["details"]["releaseId"], "release-canary");
+
+    let (status, body) = evaluate(
+        app.clone(),
+        "scoped-canary",
+        "sample-small",
+        context.clone(),
+        &primary,
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(
+        body["details"]["releaseId"], "release-primary",
+        "Callers the canary does not authorize stay on the primary release"
+    );
+
+    let (_, body) = evaluate(
+        app.clone(),
+        "overweight",
+        "sample-small",
+        context.clone(),
+        &shared,
+    )
+    .await;
+    assert_eq!(
+        body["details"]["releaseId"], "release-primary",
+        "Canary weight above 100 fails to load"
+    );
+
+    let batch = json!({ "items": [{ "context": {} }, { "context": {} }] });
+    let uri = "/api/projects/scoped-canary/evaluate-batch/sample-small";
+    let (_, body) = send(
+        app.clone(),
+        request(Method::POST, uri, Some(batch), &primary),
+    )
+    .await;
+    for item in body["results"].as_array().unwrap() {
+        assert_eq!(item["releaseId"], "release-primary");
+    }
+
+    let (status, _) = evaluate(
+        app.clone(),
+        "scoped-canary.canary",
+        "sample-small",
+        context,
+        &shared,
+    )
+    .await;
+    assert_eq!(status, 404, "Canary releases are not served by key");
+
+    let (_, body) = send(app, request(Method::GET, "/api/projects", None, &shared)).await;
+    let keys = body["projects"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["key"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec!["overweight", "scoped-canary"]);
+}
+
+async fn evaluate_stream(app: Router, body: String) -> Vec<Value> {
+    let request = Request::post("/api/projects/sample-project/evaluate-stream/sample-small")
+        .header("Content-Type", "application/x-ndjson")
+        .body(Body::from(body))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), 200, "Response should be 200.");
+
+    let byte_data = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    std::str::from_utf8(&byte_data)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str::<Value>(l).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn zip_engine_stream() {
+    let (_, app) = zip_app("tests/data", Default::default()).await;
+
+    let body = [
+        json!({ "context": { "hello": "first" } }).to_string(),
+        "not json".to_string(),
+        String::new(),
+        json!({ "key": "copy of sample-small", "context": { "hello": "third" } }).to_string(),
+    ]
+    .join("\n");
+    let lines = evaluate_stream(app, body).await;
+
+    assert_eq!(lines.len(), 3, "Blank lines are skipped");
+    assert_eq!(lines[0]["result"]["hello"], "first");
+    assert!(
+        lines[1]["error"].is_object(),
+        "Invalid line reports an error"
+    );
+    assert_eq!(lines[2]["key"], "copy of sample-small");
+    assert_eq!(lines[2]["result"]["hello"], "third");
+}
+
+#[tokio::test]
+async fn zip_engine_stream_above_body_limit() {
+    let (_, app) = zip_app("tests/data", Default::default()).await;
+
+    // 17 lines of 1 MiB each, more than the 16 MiB limit on other request bodies
+    let padding = "x".repeat(1024 * 1024);
+    let body = (0..17)
+        .map(|i| json!({ "context": { "hello": i, "padding": padding } }).to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(body.len() > 16 * 1024 * 1024);
+
+    let lines = evaluate_stream(app, body).await;
+    assert_eq!(lines.len(), 17, "Every line is evaluated");
+    for (i, line) in lines.iter().enumerate() {
+        assert_eq!(line["result"]["hello"], i);
+    }
+}
+
+fn traced_values(trace: &Value) -> Vec<&Value> {
+    trace
+        .as_object()
+        .unwrap()
+        .values()
+        .flat_map(|node| [&node["input"], &node["output"]])
+        .filter(|value| !value.is_null())
+        .collect()
+}
+
+#[tokio::test]
+async fn zip_engine_trace_redaction() {
+    let (_, app) = zip_app("tests/data-redaction", Default::default()).await;
+    let token = [("X-Access-Token", "redacted-token")];
+    let context = json!({
+        "applicant": { "name": "Jane", "ssn": "123-45-6789" },
+        "cards": [{ "number": "4111111111111111", "brand": "visa" }]
+    });
+
+    let body = json!({ "context": context, "trace": true });
+    let (status, body) = evaluate(
+        app.clone(),
+        "redacted-project",
+        "sample-small",
+        body,
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let values = traced_values(&body["trace"]);
+    assert!(!values.is_empty());
+    for value in values {
+        assert_eq!(value["applicant"]["ssn"], "[REDACTED]");
+        assert_eq!(value["applicant"]["name"], "Jane");
+        assert_eq!(value["cards"][0]["number"], "[REDACTED]");
+        assert_eq!(value["cards"][0]["brand"], "visa");
+    }
+
+    // Only the trace is redacted, the result is returned as evaluated
+    assert_eq!(body["result"]["applicant"]["ssn"], "123-45-6789");
+
+    let body = json!({ "context": context });
+    let (status, body) = evaluate(app, "redacted-project", "sample-small", body, &token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("trace").is_none_or(Value::is_null));
+}
+
+#[tokio::test]
+async fn zip_engine_failed_trace_redaction() {
+    let (_, app) = zip_app("tests/data-redaction", Default::default()).await;
+    let token = [("X-Access-Token", "redacted-token")];
+    let context = json!({ "applicant": { "name": "Jane", "ssn": "123-45-6789" } });
+
+    let body = json!({ "context": context, "trace": true });
+    let (status, body) = evaluate(app, "redacted-project", "failing-node", body, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["type"], "NodeError");
+    assert!(!body.to_string().contains("123-45-6789"));
+
+    let values = traced_values(&body["trace"]);
+    assert!(!values.is_empty());
+    for value in values {
+        assert_eq!(value["applicant"]["ssn"], "[REDACTED]");
+        assert_eq!(value["applicant"]["name"], "Jane");
+    }
+}
+
+fn policy_config(max_depth: u8, trace_policy: TracePolicy) -> EnvironmentConfig {
+    EnvironmentConfig {
+        max_depth,
+        trace_policy,
+        admin: AdminConfig {
+            tokens: vec!["admin-token".to_string()],
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn zip_engine_max_depth() {
+    let (_, app) = zip_app("tests/data-depth", policy_config(10, TracePolicy::Allow)).await;
+    let context = json!({ "context": {} });
+
+    let (status, body) = evaluate(
+        app.clone(),
+        "shallow-project",
+        "nested-0",
+        context.clone(),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Depth limit exceeded");
+
+    // A project cannot raise the agent's limit
+    let token = [("X-Access-Token", "deep-token")];
+    let (status, body) = evaluate(app, "deep-project", "nested-0", context.clone(), &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Depth limit exceeded");
+
+    let (_, app) = zip_app("tests/data-depth", policy_config(12, TracePolicy::Allow)).await;
+    let (status, _) = evaluate(
+        app.clone(),
+        "shallow-project",
+        "nested-0",
+        context.clone(),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = evaluate(
+        app.clone(),
+        "deep-project",
+        "nested-0",
+        context.clone(),
+        &token,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // but it may lower it
+    let token = [("X-Access-Token", "capped-token")];
+    let (status, body) = evaluate(app, "capped-project", "nested-0", context, &token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "Depth limit exceeded");
+}
+
+#[tokio::test]
+async fn zip_engine_trace_policy() {
+    let plain = json!({ "context": {} });
+    let traced = json!({ "context": {}, "trace": true });
+
+    let (_, app) = zip_app("tests/data-depth", policy_config(12, TracePolicy::Allow)).await;
+    let (status, body) = evaluate(app, "shallow-project", "nested-0", traced.clone(), &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["trace"].is_object());
+
+    let (_, app) = zip_app("tests/data-depth", policy_config(12, TracePolicy::Deny)).await;
+    let (status, _) = evaluate(
+        app.clone(),
+        "shallow-project",
+        "nested-0",
+        traced.clone(),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = evaluate(app, "shallow-project", "nested-0", plain, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, app) = zip_app("tests/data-depth", policy_config(12, TracePolicy::Admin)).await;
+    let (status, _) = evaluate(
+        app.clone(),
+        "shallow-project",
+        "nested-0",
+        traced.clone(),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let wrong = [("X-Admin-Token", "other-token")];
+    let (status, _) = evaluate(
+        app.clone(),
+        "shallow-project",
+        "nested-0",
+        traced.clone(),
+        &wrong,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let admin = [("X-Admin-Token", "admin-token")];
+    let (status, body) = evaluate(app, "shallow-project", "nested-0", traced, &admin).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["trace"].is_object());
+}
+
+/// Creates the agent with `evaluation_timeout`. The agent binary also limits function nodes
+/// from `EVALUATION_TIMEOUT` at startup, so the engine interrupts them after 500 ms.
+async fn timeout_app(root_dir: &str, evaluation_timeout: Duration) -> Router {
+    ZEN_CONFIG
+        .function_timeout_millis
+        .store(500, Ordering::Relaxed);
+    let config = EnvironmentConfig {
+        evaluation_timeout,
+        ..Default::default()
+    };
+
+    zip_app(root_dir, config).await.1
+}
+
+#[tokio::test]
+async fn zip_engine_timeout() {
+    let app = timeout_app("tests/data-timeout", Duration::from_millis(300)).await;
+    let context = json!({ "context": {} });
+
+    let start = Instant::now();
+    let (status, body) = evaluate(
+        app.clone(),
+        "slow-project",
+        "infinite-loop",
+        context.clone(),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert!(start.elapsed() < Duration::from_secs(3));
+    assert_eq!(body["type"], "Timeout");
+    assert_eq!(body["timeoutMs"], 300);
+
+    let (status, _) = evaluate(app, "slow-project", "sample-small", context, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn zip_engine_timeout_header() {
+    let app = timeout_app("tests/data-timeout", Duration::from_secs(10)).await;
+    let context = json!({ "context": {} });
+
+    let start = Instant::now();
+    let header = [("X-Evaluation-Timeout", "200")];
+    let (status, body) = evaluate(
+        app.clone(),
+        "slow-project",
+        "infinite-loop",
+        context.clone(),
+        &header,
+    )
+    .await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert!(start.elapsed() < Duration::from_secs(3));
+    assert_eq!(body["timeoutMs"], 200);
+
+    // The header can only lower the configured timeout
+    let app = timeout_app("tests/data-timeout", Duration::from_millis(300)).await;
+    let header = [("X-Evaluation-Timeout", "60000")];
+    let (status, body) = evaluate(
+        app.clone(),
+        "slow-project",
+        "infinite-loop",
+        context.clone(),
+        &header,
+    )
+    .await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body["timeoutMs"], 300);
+
+    let header = [("X-Evaluation-Timeout", "soon")];
+    let (status, _) = evaluate(app, "slow-project", "sample-small", context, &header).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn zip_engine_shadow_timeout() {
+    let app = timeout_app("tests/data-shadow-timeout", Duration::from_millis(300)).await;
+
+    // The candidate answers `sample-small` with an infinite loop
+    let context = json!({ "context": {} });
+    let (status, _) = evaluate(app.clone(), "slow-shadow", "sample-small", context, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let error = r#"project="slow-shadow",decision="sample-small",outcome="error""#;
+    assert!(
+        wait_for_metrics(app, &[error]).await.contains(error),
+        "Shadow evaluation is recorded as an error"
+    );
+}
+
 async fn run_engine_test(config: EnvironmentConfig, project_name: &str) {
     let agent = app::create_agent(config.clone(), Default::default()).await;
     let router = app::create_app(agent, config).await;
@@ -1,9 +1,13 @@
 mod support;
 
-use agent::app;
-use agent::config::{EnvironmentConfig, ProviderConfig, ZipProviderConfig};
-use axum::body::Body;
-use axum::http::Request;
+use agent::config::{EnvironmentConfig, ProviderConfig, ReadinessConfig, ZipProviderConfig};
+use agent::{RefreshScope, app};
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Method, Request};
+use serde_json::json;
+use std::fs;
+use support::{evaluate, request, send, temp_dir, zip_app};
 use tower::ServiceExt;
 
 #[tokio::test]
@@ -22,3 +26,138 @@ async fn health_test() {
     let response = app.oneshot(request).await.unwrap();
     assert_eq!(response.status(), 200, "Response should be 200.");
 }
+
+#[tokio::test]
+async fn ready_test() {
+    let config = EnvironmentConfig {
+        readiness: ReadinessConfig {
+            min_projects: 2,
+            required_projects: vec!["sample-project".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, app) = zip_app("tests/data", config).await;
+
+    let (status, _) = send(app, request(Method::GET, "/api/ready", None, &[])).await;
+    assert_eq!(status, 200, "Response should be 200.");
+}
+
+#[tokio::test]
+async fn not_ready_test() {
+    let config = EnvironmentConfig {
+        readiness: ReadinessConfig {
+            min_projects: 10,
+            required_projects: vec!["missing-project".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, app) = zip_app("tests/data", config).await;
+
+    let (status, body) = send(app, request(Method::GET, "/api/ready", None, &[])).await;
+    assert_eq!(status, 503, "Response should be 503.");
+    assert_eq!(body["status"], "notReady");
+    assert_eq!(
+        body["reasons"].as_array().map(Vec::len),
+        Some(2),
+        "Both the project count and the missing project are reported"
+    );
+}
+
+#[tokio::test]
+async fn degraded_ready_test() {
+    let root_dir = temp_dir("degraded");
+    fs::copy(
+        "tests/data/sample-project.zip",
+        root_dir.join("sample-project.zip"),
+    )
+    .unwrap();
+
+    let config = EnvironmentConfig {
+        readiness: ReadinessConfig {
+            max_refresh_failures: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (agent, app) = zip_app(root_dir.to_str().unwrap(), config).await;
+
+    fs::remove_dir_all(&root_dir).unwrap();
+    assert!(agent.refresh_data(RefreshScope::All).await.is_err());
+
+    let (status, body) = send(app, request(Method::GET, "/api/ready", None, &[])).await;
+    assert_eq!(status, 200, "Degraded agents stay in rotation");
+    assert_eq!(body["status"], "degraded");
+    assert_eq!(body["consecutiveRefreshFailures"], 1);
+}
+
+#[tokio::test]
+async fn shutting_down_not_ready_test() {
+    let (agent, app) = zip_app("tests/data", Default::default()).await;
+    agent.shutdown();
+
+    let (status, _) = send(app.clone(), request(Method::GET, "/api/ready", None, &[])).await;
+    assert_eq!(status, 503, "Response should be 503.");
+
+    let (status, _) = send(app, request(Method::GET, "/api/health", None, &[])).await;
+    assert_eq!(status, 200, "Liveness is unaffected by shutdown");
+}
+
+async fn metrics(app: Router) -> String {
+    let request = Request::get("/metrics").body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), 200, "Response should be 200.");
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn metrics_test() {
+    let (_, app) = zip_app("tests/data", Default::default()).await;
+
+    let context = json!({ "context": {} });
+    let (status, _) = evaluate(app.clone(), "sample-project", "sample-small", context, &[]).await;
+    assert_eq!(status, 200, "Response should be 200.");
+
+    let body = metrics(app).await;
+    assert!(
+        body.contains(
+            r#"agent_evaluations_total{project="sample-project",decision="sample-small""#
+        ),
+        "Evaluation counter is exposed"
+    );
+    assert!(
+        body.contains("agent_evaluation_duration_seconds_bucket"),
+        "Evaluation latency histogram is exposed"
+    );
+    assert!(
+        body.contains("agent_projects_loaded"),
+        "Loaded projects gauge is exposed"
+    );
+}
+
+#[tokio::test]
+async fn metrics_project_label_test() {
+    let (_, app) = zip_app("tests/data-secured", Default::default()).await;
+
+    let token = [("X-Access-Token", "secured-token")];
+    for project in ["secured-project", "7f1c2a9e-3a51-4c1e-9a5b-1d2f0c8e4b11"] {
+        let context = json!({ "context": {} });
+        let (status, _) = evaluate(app.clone(), project, "sample-small", context, &token).await;
+        assert_eq!(status, 200, "Response should be 200.");
+    }
+
+    let body = metrics(app).await;
+    assert!(
+        body.contains(
+            r#"agent_evaluations_total{project="secured-project",decision="sample-small",release_id="0b6d3c52-5f7e-4f55-8d0e-2c9a1e7f6a22"} 2"#
+        ),
+        "Requests by slug and by id share a series"
+    );
+    assert!(
+        !body.contains(r#"project="7f1c2a9e-3a51-4c1e-9a5b-1d2f0c8e4b11""#),
+        "Project ids are not used as labels"
+    );
+}
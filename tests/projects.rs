@@ -0,0 +1,166 @@
+mod support;
+
+use agent::config::EnvironmentConfig;
+use axum::Router;
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use support::{request, send, zip_app};
+
+async fn secured_app(config: EnvironmentConfig) -> Router {
+    zip_app("tests/data-secured", config).await.1
+}
+
+async fn list_project_keys(app: Router, access_token: Option<&str>) -> Vec<String> {
+    let headers = access_token.map(|token| ("X-Access-Token", token));
+    let request = request(Method::GET, "/api/projects", None, headers.as_slice());
+    let (status, body) = send(app, request).await;
+    assert_eq!(status, 200, "Response should be 200.");
+
+    body["projects"]
+        .as_array()
+        .expect("projects is an array")
+        .iter()
+        .map(|p| p["key"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn list_projects_filtered_by_token() {
+    let app = secured_app(Default::default()).await;
+
+    assert_eq!(
+        list_project_keys(app.clone(), None).await,
+        vec!["public-project"]
+    );
+    assert_eq!(
+        list_project_keys(app.clone(), Some("secured-token")).await,
+        vec!["public-project", "secured-project"]
+    );
+}
+
+#[tokio::test]
+async fn list_projects_release_details() {
+    let app = secured_app(Default::default()).await;
+
+    let headers = [("X-Access-Token", "secured-token")];
+    let (_, body) = send(app, request(Method::GET, "/api/projects", None, &headers)).await;
+
+    let secured = &body["projects"][1];
+    assert_eq!(secured["key"], "secured-project");
+    assert_eq!(secured["project_key"], "secured-project");
+    assert_eq!(secured["release_version"], "1.0.0");
+    assert_eq!(secured["decision_count"], 3);
+    assert!(secured["loaded_at"].is_string(), "Load timestamp is set");
+}
+
+async fn status(app: Router, method: Method, uri: &str, access_token: Option<&str>) -> StatusCode {
+    let mut headers = Vec::from_iter(access_token.map(|token| ("X-Access-Token", token)));
+    if uri.contains("/evaluate-stream/") {
+        headers.push(("Content-Type", "application/x-ndjson"));
+    }
+
+    let body = json!({ "context": {}, "items": [] });
+    send(app, request(method, uri, Some(body), &headers))
+        .await
+        .0
+}
+
+#[tokio::test]
+async fn project_routes_reject_invalid_token() {
+    let app = secured_app(Default::default()).await;
+    let routes = [
+        (Method::GET, "/api/projects/secured-project"),
+        (Method::GET, "/api/projects/secured-project/entrypoints"),
+        (
+            Method::POST,
+            "/api/projects/secured-project/evaluate/sample-small",
+        ),
+        (
+            Method::POST,
+            "/api/projects/secured-project/evaluate-batch/sample-small",
+        ),
+        (
+            Method::POST,
+            "/api/projects/secured-project/evaluate-stream/sample-small",
+        ),
+    ];
+
+    for (method, uri) in routes {
+        assert_eq!(
+            status(app.clone(), method.clone(), uri, Some("wrong-token")).await,
+            401,
+            "{method} {uri} with an invalid token"
+        );
+        assert_eq!(
+            status(app.clone(), method.clone(), uri, None).await,
+            401,
+            "{method} {uri} without a token"
+        );
+        assert_eq!(
+            status(app.clone(), method.clone(), uri, Some("secured-token")).await,
+            200,
+            "{method} {uri} with a valid token"
+        );
+    }
+}
+
+#[tokio::test]
+async fn metadata_auth_required() {
+    let app = secured_app(EnvironmentConfig {
+        require_metadata_auth: true,
+        ..Default::default()
+    })
+    .await;
+    let routes = [
+        "/api/projects",
+        "/api/projects/secured-project",
+        "/api/projects/public-project/entrypoints",
+        "/api.json",
+    ];
+
+    for uri in routes {
+        assert_eq!(
+            status(app.clone(), Method::GET, uri, None).await,
+            401,
+            "{uri} without a token"
+        );
+        assert_eq!(
+            status(app.clone(), Method::GET, uri, Some("wrong-token")).await,
+            401,
+            "{uri} with an invalid token"
+        );
+        assert_eq!(
+            status(app.clone(), Method::GET, uri, Some("secured-token")).await,
+            200,
+            "{uri} with a valid token"
+        );
+    }
+
+    for uri in [
+        "/api/projects/missing-project",
+        "/api/projects/missing-project/entrypoints",
+    ] {
+        assert_eq!(
+            status(app.clone(), Method::GET, uri, None).await,
+            401,
+            "{uri} without a token"
+        );
+        assert_eq!(
+            status(app.clone(), Method::GET, uri, Some("secured-token")).await,
+            404,
+            "{uri} with a valid token"
+        );
+    }
+
+    assert_eq!(
+        status(
+            app,
+            Method::POST,
+            "/api/projects/public-project/evaluate/sample-small",
+            None
+        )
+        .await,
+        200,
+        "Evaluation of public projects is unaffected"
+    );
+}
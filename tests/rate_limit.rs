@@ -0,0 +1,180 @@
+mod support;
+
+use agent::config::{EnvironmentConfig, RateLimitConfig, RateLimitKey};
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use support::{evaluate_request, send, zip_app};
+use tower::ServiceExt;
+
+fn rate_limited(key: RateLimitKey, burst: u32) -> EnvironmentConfig {
+    EnvironmentConfig {
+        rate_limit: Some(RateLimitConfig {
+            key,
+            rate: 0.01,
+            burst: Some(burst),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Evaluation of `sample-small`, sent from `ip` when given.
+fn evaluate_from(project: &str, token: Option<&str>, ip: Option<&str>) -> Request<Body> {
+    let headers = token.map(|token| ("X-Access-Token", token));
+    let body = json!({ "context": {} });
+    let mut request = evaluate_request(project, "sample-small", body, headers.as_slice());
+    if let Some(ip) = ip {
+        let address: SocketAddr = format!("{ip}:4000").parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(address));
+    }
+
+    request
+}
+
+async fn status(app: Router, project: &str, token: Option<&str>, ip: Option<&str>) -> StatusCode {
+    send(app, evaluate_from(project, token, ip)).await.0
+}
+
+#[tokio::test]
+async fn rate_limit_by_token() {
+    let app = zip_app(
+        "tests/data-rate-limit",
+        rate_limited(RateLimitKey::Token, 2),
+    )
+    .await
+    .1;
+
+    let ip = Some("10.0.0.1");
+    assert_eq!(
+        status(app.clone(), "secured-project", Some("token-a"), ip).await,
+        200
+    );
+    assert_eq!(
+        status(app.clone(), "secured-project", Some("token-a"), ip).await,
+        200
+    );
+
+    let request = evaluate_from("secured-project", Some("token-a"), ip);
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), 429, "Burst is used up");
+    let retry_after = response.headers()["Retry-After"].to_str().unwrap();
+    assert!(
+        retry_after.parse::<u64>().unwrap() >= 1,
+        "Retry-After is set"
+    );
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = serde_json::from_slice::<Value>(&body).unwrap();
+    assert_eq!(body["message"], "Rate limit exceeded");
+
+    assert_eq!(
+        status(app.clone(), "secured-project", Some("token-b"), ip).await,
+        200,
+        "Other tokens have their own bucket"
+    );
+    assert_eq!(
+        status(app.clone(), "public-project", None, None).await,
+        200,
+        "Anonymous requests fall back to the client IP"
+    );
+
+    let other_ip = Some("10.0.0.2");
+    assert_eq!(
+        status(app.clone(), "public-project", Some("made-up-1"), other_ip).await,
+        200
+    );
+    assert_eq!(
+        status(app.clone(), "secured-project", Some("made-up-2"), other_ip).await,
+        401
+    );
+    assert_eq!(
+        status(app.clone(), "public-project", Some("made-up-3"), other_ip).await,
+        429,
+        "Tokens that do not verify share the bucket of their IP"
+    );
+    assert_eq!(
+        status(app, "secured-project", Some("token-b"), other_ip).await,
+        429,
+        "Tokens are not verified once the bucket of their IP is used up"
+    );
+}
+
+#[test]
+fn rate_limit_rejects_invalid_rates() {
+    let parse = |rate: Value| serde_json::from_value::<RateLimitConfig>(json!({ "rate": rate }));
+
+    assert!(parse(json!(50)).is_ok());
+    assert!(parse(json!(0)).is_ok(), "Zero disables the limit");
+    assert!(parse(json!(1e-20)).is_err(), "Refill time would overflow");
+    assert!(parse(json!(-1)).is_err());
+    assert!(parse(json!("NaN")).is_err());
+}
+
+#[tokio::test]
+async fn rate_limit_by_ip() {
+    let app = zip_app("tests/data-rate-limit", rate_limited(RateLimitKey::Ip, 1))
+        .await
+        .1;
+
+    let ip = Some("10.0.0.1");
+    assert_eq!(
+        status(app.clone(), "public-project", Some("a"), ip).await,
+        200
+    );
+    assert_eq!(
+        status(app.clone(), "public-project", Some("b"), ip).await,
+        429,
+        "Tokens share the bucket of their IP"
+    );
+    assert_eq!(
+        status(app, "public-project", Some("a"), Some("10.0.0.2")).await,
+        200
+    );
+}
+
+#[tokio::test]
+async fn rate_limit_by_project() {
+    let app = zip_app(
+        "tests/data-rate-limit",
+        rate_limited(RateLimitKey::Project, 1),
+    )
+    .await
+    .1;
+
+    assert_eq!(status(app.clone(), "public-project", None, None).await, 200);
+    assert_eq!(status(app.clone(), "public-project", None, None).await, 429);
+    assert_eq!(
+        status(app.clone(), "missing-project", None, None).await,
+        404,
+        "Unknown projects do not share the bucket of public ones"
+    );
+    assert_eq!(
+        status(app, "secured-project", Some("token-a"), None).await,
+        200
+    );
+}
+
+#[tokio::test]
+async fn rate_limit_per_project() {
+    let app = zip_app("tests/data-rate-limit", Default::default()).await.1;
+
+    assert_eq!(
+        status(app.clone(), "limited-project", Some("limited-token"), None).await,
+        200
+    );
+    assert_eq!(
+        status(app.clone(), "limited-project", Some("other-token"), None).await,
+        429,
+        "Project limit is shared by all callers"
+    );
+
+    for _ in 0..3 {
+        assert_eq!(
+            status(app.clone(), "public-project", None, None).await,
+            200,
+            "Projects without a limit are unaffected"
+        );
+    }
+}
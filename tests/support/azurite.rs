@@ -1,7 +1,9 @@
-use crate::support::path::data_path_files;
+use crate::support::path::{data_path, data_path_files};
 use azure_storage::{CloudLocation, ConnectionString};
-use azure_storage_blobs::prelude::BlobServiceClient;
+use azure_storage_blobs::prelude::{BlobServiceClient, ContainerClient};
+use futures::{StreamExt, TryStreamExt};
 use std::borrow::Cow;
+use std::fs;
 use testcontainers::core::WaitFor;
 use testcontainers::runners::AsyncRunner;
 use testcontainers::{ContainerAsync, Image};
@@ -10,6 +12,7 @@ pub struct AzuriteContainer {
     pub container: ContainerAsync<AzuriteImage>,
     pub connection_string: String,
     pub container_name: String,
+    client: ContainerClient,
 }
 
 impl AzuriteContainer {
@@ -37,8 +40,31 @@ impl AzuriteContainer {
             container,
             connection_string: cs,
             container_name: cn,
+            client: blob_container_client,
         })
     }
+
+    /// Uploads `count` copies of the sample project as `{prefix}/project-{i}`.
+    pub async fn put_copies(
+        &self,
+        prefix: &str,
+        count: usize,
+    ) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        let buf_data = fs::read(format!("{}/sample-project.zip", data_path()))?;
+
+        futures::stream::iter(0..count)
+            .map(|i| {
+                self.client
+                    .blob_client(format!("{prefix}/project-{i}"))
+                    .put_block_blob(buf_data.clone())
+                    .into_future()
+            })
+            .buffer_unordered(50)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -1,11 +1,13 @@
-use crate::support::path::data_path_files;
+use crate::support::path::{data_path, data_path_files};
 use aws_config::BehaviorVersion;
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_s3::Client;
 use aws_sdk_s3::config::Credentials;
 use aws_sdk_s3::primitives::ByteStream;
+use futures::{StreamExt, TryStreamExt};
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fs;
 use testcontainers::core::WaitFor;
 use testcontainers::runners::AsyncRunner;
 use testcontainers::{ContainerAsync, Image};
@@ -39,6 +41,31 @@ impl MinioContainer {
 
         Ok(Self { container, client })
     }
+
+    /// Uploads `count` copies of the sample project as `{prefix}/project-{i}`.
+    pub async fn put_copies(
+        &self,
+        prefix: &str,
+        count: usize,
+    ) -> Result<(), Box<dyn std::error::Error + 'static>> {
+        let bucket = self.container.image().bucket_name.as_str();
+        let buf_data = fs::read(format!("{}/sample-project.zip", data_path()))?;
+
+        futures::stream::iter(0..count)
+            .map(|i| {
+                self.client
+                    .put_object()
+                    .bucket(bucket)
+                    .key(format!("{prefix}/project-{i}"))
+                    .body(ByteStream::from(buf_data.clone()))
+                    .send()
+            })
+            .buffer_unordered(50)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
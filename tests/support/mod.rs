@@ -1,3 +1,96 @@
 pub mod azurite;
 pub mod minio;
 pub mod path;
+
+use agent::config::{EnvironmentConfig, ProviderConfig, ZipProviderConfig};
+use agent::{Agent, app};
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use axum::http::{Method, Request, StatusCode};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::{env, fs};
+use tower::ServiceExt;
+
+/// Creates the agent and its app serving the zip releases in `root_dir`.
+pub async fn zip_app(root_dir: &str, config: EnvironmentConfig) -> (Agent, Router) {
+    let config = EnvironmentConfig {
+        provider: ProviderConfig::Zip(ZipProviderConfig {
+            root_dir: root_dir.to_string(),
+        }),
+        ..config
+    };
+
+    let agent = app::create_agent(config.clone(), Default::default()).await;
+    (agent.clone(), app::create_app(agent, config).await)
+}
+
+/// Creates an empty directory under the temp dir, unique to `name` and the test process.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("agent-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Builds a request with a JSON `body`, if any, and the given headers. Bodies are sent as
+/// `application/json` unless the headers set another content type.
+pub fn request(
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+    headers: &[(&'static str, &str)],
+) -> Request<Body> {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri.replace(' ', "%20"));
+    let content_type = headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("Content-Type"));
+    if body.is_some() && !content_type {
+        request = request.header("Content-Type", "application/json");
+    }
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+
+    let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+    request.body(body).unwrap()
+}
+
+pub fn evaluate_uri(project: &str, decision: &str) -> String {
+    format!("/api/projects/{project}/evaluate/{decision}").replace(' ', "%20")
+}
+
+pub fn evaluate_request(
+    project: &str,
+    decision: &str,
+    body: Value,
+    headers: &[(&'static str, &str)],
+) -> Request<Body> {
+    request(
+        Method::POST,
+        &evaluate_uri(project, decision),
+        Some(body),
+        headers,
+    )
+}
+
+/// Sends the request and returns the status with the JSON body, `Null` if there is none.
+pub async fn send(app: Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Evaluates `decision` and returns the status with the JSON body, `Null` if there is none.
+pub async fn evaluate(
+    app: Router,
+    project: &str,
+    decision: &str,
+    body: Value,
+    headers: &[(&'static str, &str)],
+) -> (StatusCode, Value) {
+    send(app, evaluate_request(project, decision, body, headers)).await
+}
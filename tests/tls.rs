@@ -0,0 +1,187 @@
+mod support;
+
+use agent::config::HttpSslConfig;
+use agent::tls::{self, ClientCertAcceptor};
+use axum_server::Handle;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use reqwest::tls::TlsInfo;
+use reqwest::{Certificate, Client, Identity};
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::pem::PemObject;
+use std::fs;
+use std::net::SocketAddr;
+use std::time::Duration;
+use support::{evaluate_uri, zip_app};
+
+const CA: &[u8] = include_bytes!("data-mtls/certs/ca.pem");
+
+async fn tls_server(ssl: HttpSslConfig) -> SocketAddr {
+    rustls::crypto::aws_lc_rs::default_provider()
+        .install_default()
+        .ok();
+
+    let rustls_config = ssl.to_rustls_config().await.unwrap();
+    tls::reload_certificates(ssl, rustls_config.clone(), Duration::from_millis(50));
+
+    let (_, app) = zip_app("tests/data-mtls", Default::default()).await;
+
+    let handle = Handle::new();
+    let server = axum_server::bind("127.0.0.1:0".parse::<SocketAddr>().unwrap())
+        .acceptor(ClientCertAcceptor::new(rustls_config))
+        .handle(handle.clone());
+    tokio::spawn(server.serve(app.into_make_service()));
+
+    handle.listening().await.expect("Server is listening")
+}
+
+async fn mtls_server(client_auth_optional: bool) -> SocketAddr {
+    tls_server(HttpSslConfig {
+        cert: Some(BASE64_STANDARD.encode(include_bytes!("data-mtls/certs/server.pem"))),
+        key: Some(BASE64_STANDARD.encode(include_bytes!("data-mtls/certs/server.key"))),
+        client_ca: Some(BASE64_STANDARD.encode(CA)),
+        client_auth_optional,
+        ..Default::default()
+    })
+    .await
+}
+
+fn client(identity: Option<&str>) -> Client {
+    let mut builder = Client::builder()
+        .use_rustls_tls()
+        .add_root_certificate(Certificate::from_pem(CA).unwrap());
+
+    if let Some(name) = identity {
+        let cert = std::fs::read(format!("tests/data-mtls/certs/{name}.pem")).unwrap();
+        let key = std::fs::read(format!("tests/data-mtls/certs/{name}.key")).unwrap();
+        builder = builder.identity(Identity::from_pem(&[cert, key].concat()).unwrap());
+    }
+
+    builder.build().unwrap()
+}
+
+/// Evaluates over HTTPS, so the server sees the client certificate.
+async fn evaluate_over_tls(
+    address: SocketAddr,
+    identity: Option<&str>,
+    token: Option<&str>,
+) -> reqwest::Result<u16> {
+    let uri = evaluate_uri("mtls-project", "sample-small");
+    let mut request = client(identity)
+        .post(format!("https://localhost:{}{uri}", address.port()))
+        .json(&serde_json::json!({ "context": {} }));
+    if let Some(token) = token {
+        request = request.header("X-Access-Token", token);
+    }
+
+    Ok(request.send().await?.status().as_u16())
+}
+
+#[tokio::test]
+async fn mtls_client_identity() {
+    let address = mtls_server(false).await;
+
+    assert_eq!(
+        evaluate_over_tls(address, Some("billing"), None)
+            .await
+            .unwrap(),
+        200,
+        "SAN is listed in clientIdentities"
+    );
+    assert_eq!(
+        evaluate_over_tls(address, Some("other"), None)
+            .await
+            .unwrap(),
+        401,
+        "Certificate is valid but not granted the project"
+    );
+    assert_eq!(
+        evaluate_over_tls(address, Some("other"), Some("mtls-token"))
+            .await
+            .unwrap(),
+        200,
+        "Access tokens still apply"
+    );
+    assert!(
+        evaluate_over_tls(address, None, Some("mtls-token"))
+            .await
+            .is_err(),
+        "Client certificate is required"
+    );
+}
+
+#[tokio::test]
+async fn mtls_client_auth_optional() {
+    let address = mtls_server(true).await;
+
+    assert_eq!(
+        evaluate_over_tls(address, None, Some("mtls-token"))
+            .await
+            .unwrap(),
+        200
+    );
+    assert_eq!(evaluate_over_tls(address, None, None).await.unwrap(), 401);
+    assert_eq!(
+        evaluate_over_tls(address, Some("billing"), None)
+            .await
+            .unwrap(),
+        200
+    );
+}
+
+async fn served_certificate(address: SocketAddr) -> Vec<u8> {
+    let ca = fs::read("tests/data-tls/ca.pem").unwrap();
+    let client = Client::builder()
+        .use_rustls_tls()
+        .add_root_certificate(Certificate::from_pem(&ca).unwrap())
+        .tls_info(true)
+        .build()
+        .unwrap();
+
+    let url = format!("https://localhost:{}/api/health", address.port());
+    let response = client.get(url).send().await.unwrap();
+    let tls_info = response.extensions().get::<TlsInfo>().unwrap();
+
+    tls_info.peer_certificate().unwrap().to_vec()
+}
+
+fn certificate(name: &str) -> Vec<u8> {
+    let path = format!("tests/data-tls/{name}.pem");
+    CertificateDer::from_pem_file(path).unwrap().to_vec()
+}
+
+#[tokio::test]
+async fn tls_certificate_reload() {
+    let dir = std::env::temp_dir().join(format!("agent-tls-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let install = |name: &str| {
+        fs::copy(format!("tests/data-tls/{name}.pem"), dir.join("cert.pem")).unwrap();
+        fs::copy(format!("tests/data-tls/{name}.key"), dir.join("key.pem")).unwrap();
+    };
+
+    install("server-a");
+    let address = tls_server(HttpSslConfig {
+        cert_file: Some(dir.join("cert.pem")),
+        key_file: Some(dir.join("key.pem")),
+        ..Default::default()
+    })
+    .await;
+    assert_eq!(served_certificate(address).await, certificate("server-a"));
+
+    install("server-b");
+    let mut served = Vec::new();
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        served = served_certificate(address).await;
+        if served == certificate("server-b") {
+            break;
+        }
+    }
+
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(
+        served,
+        certificate("server-b"),
+        "Rotated certificate is served"
+    );
+}